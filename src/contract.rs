@@ -1,18 +1,425 @@
-use std::str::from_utf8;
-
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{to_binary, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult};
-use cw2::set_contract_version;
+use cosmwasm_std::{
+    coins, to_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, Event,
+    MessageInfo, Order, QuerierWrapper, Response, StdError, StdResult, Storage, Timestamp, Uint128,
+    WasmMsg,
+};
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
 
 use crate::error::ContractError;
-use crate::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use crate::state::{State, STATE};
+use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, Recipient};
+use crate::state::{
+    AssetInfo, DenomInfo, FeeRecipient, State, VestingSchedule, BALANCES, CW20_PREFIX, DENOM_FEES,
+    ESCROWS_BY_RECIPIENT, ESCROWS_BY_SENDER, FEE_BOUNDS, PENDING_CONFIG, PENDING_DENOM_FEES,
+    PENDING_FEE_BOUNDS, STATE, SUPPORTED_DENOMS, VESTING_SCHEDULES,
+};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:transfer-contract";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+// default number of seconds a fee update is queued before it takes effect
+const DEFAULT_FEE_UPDATE_DELAY: u64 = 86_400;
+
+// default maximum number of recipients of a single split send
+const DEFAULT_MAX_RECIPIENTS: u32 = 20;
+
+// maximum number of unvested schedules a single sender can lock the balance of an account in a single denom with
+const MAX_VESTING_SCHEDULES: u32 = 20;
+
+// default and maximum page sizes of paginated queries
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
+
+// fees are expressed in basis points, i.e. 10_000 is 100% of the transaction funds
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+/// State.fees is the number of basis points of transaction funds that will be sent to the owner in send
+/// transactions. As such, it must be at most 10_000 because in send the logic does fees / 10_000 when
+/// distributing funds.
+fn validate_fees(fees: u16) -> Result<(), ContractError> {
+    if fees > BPS_DENOMINATOR {
+        return Err(ContractError::InvalidFeePercentageError { fees });
+    }
+    Ok(())
+}
+
+/// The referral share is a number of basis points of the fee, so like the fees it can be at most 100%
+fn validate_referral_share(referral_share: u16) -> Result<(), ContractError> {
+    if referral_share > BPS_DENOMINATOR {
+        return Err(ContractError::InvalidReferralShareError { referral_share });
+    }
+    Ok(())
+}
+
+/// Returns the fees charged on a denom, which is the denom's own fees if the owner has set them and the
+/// global fees otherwise.
+fn fees_for_denom(storage: &dyn Storage, state: &State, denom: &str) -> StdResult<u16> {
+    Ok(DENOM_FEES
+        .may_load(storage, denom.to_owned())?
+        .unwrap_or(state.fees))
+}
+
+/// Returns the fee charged on an amount of a denom. The fee is the denom's fees in basis points rounded
+/// down, raised to the minimum fee and lowered to the maximum fee if the owner has bounded it. Amounts that
+/// cannot cover the minimum fee are rejected.
+fn fee_amount(
+    storage: &dyn Storage,
+    state: &State,
+    amount: Uint128,
+    denom: &str,
+) -> Result<Uint128, ContractError> {
+    let fees: u16 = fees_for_denom(storage, state, denom)?;
+    let mut fee: Uint128 = amount.multiply_ratio(fees, BPS_DENOMINATOR);
+
+    if let Some(bounds) = FEE_BOUNDS.may_load(storage, denom.to_owned())? {
+        if let Some(min_fee) = bounds.min_fee {
+            if amount < min_fee {
+                return Err(ContractError::BelowMinimumFeeError {
+                    denom: denom.to_owned(),
+                    amount,
+                    min_fee,
+                });
+            }
+            fee = fee.max(min_fee);
+        }
+        if let Some(max_fee) = bounds.max_fee {
+            fee = fee.min(max_fee);
+        }
+    }
+
+    Ok(fee)
+}
+
+/// A send must include funds and every coin sent must have a non-zero amount.
+fn validate_send_funds(funds: &[Coin]) -> Result<(), ContractError> {
+    if funds.is_empty() {
+        return Err(ContractError::NoFundsError {});
+    }
+    if let Some(coin) = funds.iter().find(|coin| coin.amount.is_zero()) {
+        return Err(ContractError::ZeroAmountError {
+            denom: coin.denom.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks every coin sent against the denom registry. In allowlist mode only registered denoms can be sent,
+/// and registered denoms with a minimum send amount reject smaller sends in either mode.
+fn ensure_supported_funds(
+    storage: &dyn Storage,
+    state: &State,
+    funds: &[Coin],
+) -> Result<(), ContractError> {
+    for coin in funds {
+        match SUPPORTED_DENOMS.may_load(storage, coin.denom.clone())? {
+            Some(DenomInfo {
+                min_send: Some(min_send),
+                ..
+            }) if coin.amount < min_send => {
+                return Err(ContractError::BelowMinimumSendError {
+                    denom: coin.denom.clone(),
+                    amount: coin.amount,
+                    min_send,
+                });
+            }
+            None if state.denom_allowlist => {
+                return Err(ContractError::UnsupportedDenomError {
+                    denom: coin.denom.clone(),
+                });
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Returns the vesting schedules locking the balance of a denom of an account along with their ids. Each sender
+/// can add at most MAX_VESTING_SCHEDULES of them that have not fully vested.
+fn vesting_schedules(
+    storage: &dyn Storage,
+    address: &Addr,
+    denom: &str,
+) -> StdResult<Vec<(u64, VestingSchedule)>> {
+    VESTING_SCHEDULES
+        .prefix((address, denom.to_owned()))
+        .range(storage, None, None, Order::Ascending)
+        .collect()
+}
+
+/// Removes the vesting schedules of a denom of an account that are fully vested, as they no longer lock anything.
+fn prune_vesting_schedules(
+    storage: &mut dyn Storage,
+    address: &Addr,
+    denom: &str,
+    time: Timestamp,
+) -> StdResult<()> {
+    for (id, schedule) in vesting_schedules(storage, address, denom)? {
+        if schedule.vested(time) == schedule.total {
+            VESTING_SCHEDULES.remove(storage, (address, denom.to_owned(), id));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the amount of a denom in the balance of an account that is still locked by its vesting schedules.
+fn locked_amount(
+    storage: &dyn Storage,
+    address: &Addr,
+    denom: &str,
+    time: Timestamp,
+) -> StdResult<Uint128> {
+    Ok(vesting_schedules(storage, address, denom)?
+        .iter()
+        .map(|(_, schedule)| schedule.total - schedule.vested(time))
+        .sum())
+}
+
+/// Returns the amount of a denom an account can withdraw, which is its balance less what is still unvested.
+fn withdrawable_amount(
+    storage: &dyn Storage,
+    address: &Addr,
+    denom: &str,
+    time: Timestamp,
+) -> StdResult<Uint128> {
+    let balance = BALANCES
+        .may_load(storage, (address, denom.to_owned()))?
+        .unwrap_or_default();
+    Ok(balance.saturating_sub(locked_amount(storage, address, denom, time)?))
+}
+
+/// Checks that an account can withdraw an amount of a denom, failing if its balance is insufficient or if
+/// part of the amount has not vested yet.
+fn ensure_withdrawable(
+    storage: &dyn Storage,
+    address: &Addr,
+    amount: Uint128,
+    denom: &str,
+    time: Timestamp,
+) -> Result<(), ContractError> {
+    let balance = BALANCES
+        .may_load(storage, (address, denom.to_owned()))?
+        .unwrap_or_default();
+    if amount > balance {
+        return Err(ContractError::InsufficientBalanceError {
+            denom: denom.to_owned(),
+            balance,
+            requested: amount,
+        });
+    }
+
+    let withdrawable = withdrawable_amount(storage, address, denom, time)?;
+    if amount > withdrawable {
+        return Err(ContractError::UnvestedBalanceError {
+            denom: denom.to_owned(),
+            withdrawable,
+            requested: amount,
+        });
+    }
+    Ok(())
+}
+
+/// Validates the addresses and weights of a recipient list, which must not be longer than max_recipients,
+/// give every recipient a non-zero weight or list the same address twice.
+fn validate_recipients(
+    api: &dyn Api,
+    recipients: Vec<Recipient>,
+    max_recipients: u32,
+) -> Result<Vec<(Addr, u64)>, ContractError> {
+    if recipients.len() > max_recipients as usize {
+        return Err(ContractError::TooManyRecipientsError {
+            count: recipients.len() as u32,
+            max: max_recipients,
+        });
+    }
+
+    let mut weighted: Vec<(Addr, u64)> = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let address: Addr = api.addr_validate(&recipient.address)?;
+        if recipient.weight == 0 {
+            return Err(ContractError::ZeroWeightError {
+                address: address.to_string(),
+            });
+        }
+        if weighted.iter().any(|(existing, _)| *existing == address) {
+            return Err(ContractError::DuplicateRecipientError {
+                address: address.to_string(),
+            });
+        }
+        weighted.push((address, recipient.weight));
+    }
+
+    Ok(weighted)
+}
+
+/// Returns the address that collects the fees, which is the owner unless a fee recipient is set.
+fn fee_recipient(state: &State) -> Addr {
+    state.fee_recipient.clone().unwrap_or_else(|| state.owner.clone())
+}
+
+/// Returns the addresses the fees are split between, which is only the fee recipient if no weighted fee
+/// recipients are set. The fee recipient and the weighted fee recipients are never both set.
+fn fee_recipients(state: &State) -> Vec<(Addr, u64)> {
+    if state.fee_recipients.is_empty() {
+        return vec![(fee_recipient(state), 1)];
+    }
+    state
+        .fee_recipients
+        .iter()
+        .map(|recipient| (recipient.address.clone(), recipient.weight))
+        .collect()
+}
+
+/// Deposits and withdrawals can be paused independently so that users can always exit while deposits are
+/// paused.
+fn ensure_not_paused(paused: bool, action: &str) -> Result<(), ContractError> {
+    if paused {
+        return Err(ContractError::PausedError {
+            action: action.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+/// Loads the state, first applying any queued global or per-denom fee update and any queued fee bounds
+/// whose activation time has been reached. An event announcing each activation is returned alongside the
+/// state.
+fn load_active_state(storage: &mut dyn Storage, env: &Env) -> StdResult<(State, Vec<Event>)> {
+    let mut state: State = STATE.load(storage)?;
+    let mut events: Vec<Event> = vec![];
+
+    if let Some(pending) = PENDING_CONFIG.may_load(storage)? {
+        if pending.activates_at <= env.block.time {
+            events.push(
+                Event::new("fee_update_activated")
+                    .add_attribute("previous_fees", state.fees.to_string())
+                    .add_attribute("fees", pending.fees.to_string()),
+            );
+
+            state.fees = pending.fees;
+            STATE.save(storage, &state)?;
+            PENDING_CONFIG.remove(storage);
+        }
+    }
+
+    let due = PENDING_DENOM_FEES
+        .range(storage, None, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((_, pending)) => pending.activates_at <= env.block.time,
+            Err(_) => true,
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    for (denom, pending) in due {
+        let previous_fees: u16 = fees_for_denom(storage, &state, &denom)?;
+        match pending.fees {
+            Some(fees) => DENOM_FEES.save(storage, denom.clone(), &fees)?,
+            None => DENOM_FEES.remove(storage, denom.clone()),
+        }
+        PENDING_DENOM_FEES.remove(storage, denom.clone());
+
+        events.push(
+            Event::new("fee_update_activated")
+                .add_attribute("denom", &denom)
+                .add_attribute("previous_fees", previous_fees.to_string())
+                .add_attribute("fees", fees_for_denom(storage, &state, &denom)?.to_string()),
+        );
+    }
+
+    let due = PENDING_FEE_BOUNDS
+        .range(storage, None, None, Order::Ascending)
+        .filter(|item| match item {
+            Ok((_, pending)) => pending.activates_at <= env.block.time,
+            Err(_) => true,
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    for (denom, pending) in due {
+        let bounds = pending.bounds;
+        if bounds.min_fee.is_none() && bounds.max_fee.is_none() {
+            FEE_BOUNDS.remove(storage, denom.clone());
+        } else {
+            FEE_BOUNDS.save(storage, denom.clone(), &bounds)?;
+        }
+        PENDING_FEE_BOUNDS.remove(storage, denom.clone());
+
+        events.push(
+            Event::new("fee_bounds_update_activated")
+                .add_attribute("denom", denom)
+                .add_attribute("min_fee", optional_amount(bounds.min_fee))
+                .add_attribute("max_fee", optional_amount(bounds.max_fee)),
+        );
+    }
+
+    Ok((state, events))
+}
+
+/// Formats an optional amount as an attribute value.
+fn optional_amount(amount: Option<Uint128>) -> String {
+    amount.map(|amount| amount.to_string()).unwrap_or_else(|| "none".to_owned())
+}
+
+/// Returns the amount of the asset behind a denom held by an address, querying the token contract for
+/// CW20 tokens and the bank module for native coins.
+fn query_asset_balance(querier: &QuerierWrapper, address: &Addr, denom: &str) -> StdResult<Uint128> {
+    match AssetInfo::from_denom(denom) {
+        AssetInfo::Native(denom) => Ok(querier.query_balance(address, denom)?.amount),
+        AssetInfo::Cw20(contract_addr) => {
+            let res: BalanceResponse = querier.query_wasm_smart(
+                contract_addr,
+                &Cw20QueryMsg::Balance {
+                    address: address.to_string(),
+                },
+            )?;
+            Ok(res.balance)
+        }
+    }
+}
+
+/// Builds the message paying out an amount of the asset behind a denom, a CW20 transfer for CW20 tokens
+/// and a bank send for native coins. If a msg is given the recipient must be a contract, which is executed
+/// with the funds attached (or sent the tokens with a CW20 send).
+fn transfer_msg(
+    recipient: &Addr,
+    amount: Uint128,
+    denom: &str,
+    msg: Option<Binary>,
+) -> StdResult<CosmosMsg> {
+    let transfer: CosmosMsg = match (AssetInfo::from_denom(denom), msg) {
+        (AssetInfo::Native(denom), None) => BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: coins(amount.u128(), denom),
+        }
+        .into(),
+        (AssetInfo::Native(denom), Some(msg)) => WasmMsg::Execute {
+            contract_addr: recipient.to_string(),
+            msg,
+            funds: coins(amount.u128(), denom),
+        }
+        .into(),
+        (AssetInfo::Cw20(contract_addr), None) => WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }
+        .into(),
+        (AssetInfo::Cw20(contract_addr), Some(msg)) => WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            msg: to_binary(&Cw20ExecuteMsg::Send {
+                contract: recipient.to_string(),
+                amount,
+                msg,
+            })?,
+            funds: vec![],
+        }
+        .into(),
+    };
+    Ok(transfer)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -20,15 +427,43 @@ pub fn instantiate(
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
-    // State.fees is the percentage of transaction funds that will be sent to the over in send transactions.
-    // As such, it must be less than 100 because in send the logic does msg.fees / 100 when distributing funds.
-    if msg.fees > 100 {
-        return Err(ContractError::InvalidFeePercentageError { fees: msg.fees });
+    validate_fees(msg.fees)?;
+    let referral_share = msg.referral_share.unwrap_or(0);
+    validate_referral_share(referral_share)?;
+
+    let pauser = msg
+        .pauser
+        .map(|pauser| deps.api.addr_validate(&pauser))
+        .transpose()?;
+
+    let fee_recipient = msg
+        .fee_recipient
+        .map(|fee_recipient| deps.api.addr_validate(&fee_recipient))
+        .transpose()?;
+
+    let max_recipients = msg.max_recipients.unwrap_or(DEFAULT_MAX_RECIPIENTS);
+    let fee_recipients =
+        validate_recipients(deps.api, msg.fee_recipients.unwrap_or_default(), max_recipients)?
+            .into_iter()
+            .map(|(address, weight)| FeeRecipient { address, weight })
+            .collect::<Vec<_>>();
+    if fee_recipient.is_some() && !fee_recipients.is_empty() {
+        return Err(ContractError::ConflictingFeeRecipientsError {});
     }
 
     let state = State {
         owner: info.sender.clone(),
         fees: msg.fees,
+        fee_update_delay: msg.fee_update_delay.unwrap_or(DEFAULT_FEE_UPDATE_DELAY),
+        max_recipients,
+        pauser,
+        deposits_paused: false,
+        withdrawals_paused: false,
+        charge_transfer_fees: msg.charge_transfer_fees.unwrap_or(false),
+        fee_recipients,
+        fee_recipient,
+        referral_share,
+        denom_allowlist: msg.denom_allowlist.unwrap_or(false),
     };
 
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
@@ -38,7 +473,9 @@ pub fn instantiate(
     let res = Response::new()
         .add_attribute("method", "instantiate")
         .add_attribute("owner", info.sender)
-        .add_attribute("fees", from_utf8(&[msg.fees]).unwrap());
+        .add_attribute("fees", msg.fees.to_string())
+        .add_attribute("fee_update_delay", state.fee_update_delay.to_string())
+        .add_attribute("max_recipients", state.max_recipients.to_string());
 
     Ok(res)
 }
@@ -46,471 +483,5498 @@ pub fn instantiate(
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn execute(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    // CW20 tokens are only ever credited through Receive, so native coins must not be able to pose as one
+    if let Some(coin) = info.funds.iter().find(|coin| coin.denom.starts_with(CW20_PREFIX)) {
+        return Err(ContractError::InvalidNativeDenomError {
+            denom: coin.denom.clone(),
+        });
+    }
+
     match msg {
-        ExecuteMsg::Send { account1, account2 } => execute::send(deps, info, account1, account2),
-        ExecuteMsg::Withdraw { amount, denom } => execute::withdraw(deps, info, amount, denom),
-        ExecuteMsg::WithdrawAll { denom } => execute::withdraw_all(deps, info, denom),
-    }   
+        ExecuteMsg::Send {
+            account1,
+            account2,
+            referrer,
+        } => execute::send(deps, env, info, account1, account2, referrer),
+        ExecuteMsg::SendSplit { recipients } => execute::send_split(deps, env, info, recipients),
+        ExecuteMsg::SendVesting {
+            account1,
+            account2,
+            start,
+            cliff,
+            end,
+        } => execute::send_vesting(deps, env, info, account1, account2, start, cliff, end),
+        ExecuteMsg::SendEscrow {
+            recipients,
+            expires,
+        } => execute::send_escrow(deps, env, info, recipients, expires),
+        ExecuteMsg::Claim { id } => execute::claim(deps, env, info, id),
+        ExecuteMsg::Refund { id } => execute::refund(deps, env, info, id),
+        ExecuteMsg::SendWithArbiter {
+            recipient,
+            arbiter,
+            expires,
+        } => execute::send_with_arbiter(deps, env, info, recipient, arbiter, expires),
+        ExecuteMsg::Approve { id } => execute::approve(deps, env, info, id),
+        ExecuteMsg::Reject { id } => execute::reject(deps, info, id),
+        ExecuteMsg::RefundPayment { id } => execute::refund_payment(deps, env, id),
+        ExecuteMsg::Receive(msg) => execute::receive(deps, env, info, msg),
+        ExecuteMsg::Withdraw {
+            amount,
+            denom,
+            recipient,
+            msg,
+        } => execute::withdraw(deps, env, info, amount, denom, recipient, msg),
+        ExecuteMsg::WithdrawAll {
+            denom,
+            recipient,
+            msg,
+        } => execute::withdraw_all(deps, env, info, denom, recipient, msg),
+        ExecuteMsg::WithdrawMany { coins } => execute::withdraw_many(deps, env, info, coins),
+        ExecuteMsg::WithdrawEverything {} => execute::withdraw_everything(deps, env, info),
+        ExecuteMsg::TransferBalance { to, amount, denom } => {
+            execute::transfer_balance(deps, env, info, to, amount, denom)
+        }
+        ExecuteMsg::IncreaseAllowance {
+            spender,
+            denom,
+            amount,
+            expires,
+        } => execute::update_allowance(deps, env, info, spender, denom, amount, expires, true),
+        ExecuteMsg::DecreaseAllowance {
+            spender,
+            denom,
+            amount,
+            expires,
+        } => execute::update_allowance(deps, env, info, spender, denom, amount, expires, false),
+        ExecuteMsg::WithdrawFrom {
+            owner,
+            amount,
+            denom,
+            recipient,
+        } => execute::withdraw_from(deps, env, info, owner, amount, denom, recipient),
+        ExecuteMsg::ProposeNewOwner { new_owner, expires } => {
+            execute::propose_new_owner(deps, env, info, new_owner, expires)
+        }
+        ExecuteMsg::AcceptOwnership {} => execute::accept_ownership(deps, env, info),
+        ExecuteMsg::CancelOwnershipProposal {} => execute::cancel_ownership_proposal(deps, info),
+        ExecuteMsg::UpdateFees { fees } => execute::update_fees(deps, env, info, fees),
+        ExecuteMsg::UpdateFeeRecipients { recipients } => {
+            execute::update_fee_recipients(deps, info, recipients)
+        }
+        ExecuteMsg::UpdateFeeRecipient { fee_recipient } => {
+            execute::update_fee_recipient(deps, info, fee_recipient)
+        }
+        ExecuteMsg::UpdateReferralShare { referral_share } => {
+            execute::update_referral_share(deps, info, referral_share)
+        }
+        ExecuteMsg::RegisterDenom {
+            denom,
+            display_name,
+            decimals,
+            min_send,
+        } => execute::register_denom(deps, info, denom, display_name, decimals, min_send),
+        ExecuteMsg::DeregisterDenom { denom } => execute::deregister_denom(deps, info, denom),
+        ExecuteMsg::SetDenomAllowlist { enabled } => {
+            execute::set_denom_allowlist(deps, info, enabled)
+        }
+        ExecuteMsg::SetDenomFee { denom, fees } => {
+            execute::queue_denom_fee(deps, env, info, denom, Some(fees))
+        }
+        ExecuteMsg::SetFeeBounds {
+            denom,
+            min_fee,
+            max_fee,
+        } => execute::set_fee_bounds(deps, env, info, denom, min_fee, max_fee),
+        ExecuteMsg::ClearDenomFee { denom } => execute::queue_denom_fee(deps, env, info, denom, None),
+        ExecuteMsg::AddFeeExempt { address } => execute::update_fee_exempt(deps, info, address, true),
+        ExecuteMsg::RemoveFeeExempt { address } => {
+            execute::update_fee_exempt(deps, info, address, false)
+        }
+        ExecuteMsg::SweepSurplus { denom, recipient } => {
+            execute::sweep_surplus(deps, env, info, denom, recipient)
+        }
+        ExecuteMsg::Pause {
+            deposits,
+            withdrawals,
+        } => execute::set_paused(deps, info, deposits, withdrawals, true),
+        ExecuteMsg::Unpause {
+            deposits,
+            withdrawals,
+        } => execute::set_paused(deps, info, deposits, withdrawals, false),
+        ExecuteMsg::UpdatePauser { pauser } => execute::update_pauser(deps, info, pauser),
+    }
 }
 
 pub mod execute {
-    use std::ops::Shr;
+    use cosmwasm_std::{from_binary, Empty};
+    use cw20::Cw20ReceiveMsg;
+    use cw_utils::{nonpayable, Expiration};
 
-    use cosmwasm_std::{coins, Addr, BankMsg, Uint128};
-
-    use crate::state::BALANCES;
+    use crate::msg::ReceiveMsg;
+    use crate::state::{
+        ArbiterPayment, Escrow, EscrowShare, FeeBounds, PendingConfig, PendingDenomFee,
+        PendingFeeBounds, PendingOwner, ALLOWANCES, ARBITER_PAYMENTS, ARBITER_PAYMENT_COUNT, ESCROWS,
+        ESCROW_COUNT, FEE_EXEMPT, PENDING_OWNER, REFERRAL_EARNINGS, TOTAL_LIABILITIES,
+        VESTING_SCHEDULE_COUNT,
+    };
 
     use super::*;
 
     pub fn send(
         deps: DepsMut,
+        env: Env,
         info: MessageInfo,
         account1: String,
         account2: String,
+        referrer: Option<String>,
     ) -> Result<Response, ContractError> {
         // Validating the two addresses that will have an allowance
         let address1: Addr = deps.api.addr_validate(&account1)?;
         let address2: Addr = deps.api.addr_validate(&account2)?;
+        if address1 == address2 {
+            return Err(ContractError::DuplicateRecipientError {
+                address: address2.to_string(),
+            });
+        }
+        validate_send_funds(&info.funds)?;
+        let referrer: Option<Addr> = referrer
+            .map(|referrer| deps.api.addr_validate(&referrer))
+            .transpose()?;
+        if referrer.as_ref() == Some(&info.sender) {
+            return Err(ContractError::SelfReferralError {});
+        }
 
-        let state: State = STATE.load(deps.storage)?;
-        let fees: Uint128 = Uint128::from(state.fees);
-
-        // Iterating through all of the coins for distribution
-        for coin in info.funds.iter() {
-            // Updating the owners balance
-            let owner_fees: Uint128 = coin.amount.multiply_ratio(fees, Uint128::new(100));
-            BALANCES.update(
-                deps.storage,
-                (&state.owner, coin.denom.clone()),
-                |balance: Option<Uint128>| -> StdResult<_> {
-                    Ok(balance.unwrap_or_default() + owner_fees)
-                },
-            )?;
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        ensure_not_paused(state.deposits_paused, "deposits")?;
 
-            // Updating the remaining balances
-            let left_over: Uint128 = coin.amount - owner_fees;
-            let split_amount: Uint128 = left_over.shr(1);
-            let left_over: Uint128 = left_over - split_amount;
-            BALANCES.update(
-                deps.storage,
-                (&address1, coin.denom.clone()),
-                |balance: Option<Uint128>| -> StdResult<_> {
-                    Ok(balance.unwrap_or_default() + split_amount)
-                },
-            )?;
-            BALANCES.update(
-                deps.storage,
-                (&address2, coin.denom.clone()),
-                |balance: Option<Uint128>| -> StdResult<_> {
-                    Ok(balance.unwrap_or_default() + left_over)
-                },
-            )?;
-        }
+        // An even split, where the odd unit of dust goes to the second account
+        let exempt: bool = FEE_EXEMPT.has(deps.storage, &info.sender);
+        let recipients = vec![(address1.clone(), 1), (address2.clone(), 1)];
+        distribute_funds(
+            deps.storage,
+            &state,
+            &info.funds,
+            &recipients,
+            exempt,
+            referrer.as_ref(),
+        )?;
 
-        let res = Response::new()
+        let mut res = Response::new()
+            .add_events(activated)
+            .add_events(fee_exempt_event(exempt, &info.sender))
             .add_attribute("method", "send")
             .add_attribute("sender", &info.sender)
             .add_attribute("address_1", &address1)
             .add_attribute("address_2", &address2);
+        if let Some(referrer) = referrer {
+            res = res.add_attribute("referrer", referrer);
+        }
 
         Ok(res)
     }
 
-    pub fn withdraw(
+    pub fn send_split(
         deps: DepsMut,
+        env: Env,
         info: MessageInfo,
-        amount: Uint128,
-        denom: String,
+        recipients: Vec<Recipient>,
     ) -> Result<Response, ContractError> {
-        let balance = BALANCES
-            .may_load(deps.storage, (&info.sender, denom.clone()))?
-            .unwrap_or_default();
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        ensure_not_paused(state.deposits_paused, "deposits")?;
 
-        if amount > balance {
-            return Err(ContractError::InsufficientBalanceError {
-                balance: balance,
-                requested: amount,
-            });
+        if recipients.is_empty() {
+            return Err(ContractError::NoRecipientsError {});
         }
+        validate_send_funds(&info.funds)?;
 
-        BALANCES.update(
-            deps.storage,
-            (&info.sender, denom.clone()),
-            |balance: Option<Uint128>| -> StdResult<_> {
-                Ok(balance.unwrap_or_default().checked_sub(amount)?)
-            },
-        )?;
+        // Validating every recipient address and weight
+        let weighted = validate_recipients(deps.api, recipients, state.max_recipients)?;
+
+        let exempt: bool = FEE_EXEMPT.has(deps.storage, &info.sender);
+        distribute_funds(deps.storage, &state, &info.funds, &weighted, exempt, None)?;
 
         let res = Response::new()
-            .add_message(BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount: coins(amount.u128(), denom),
-            })
-            .add_attribute("withdraw", &info.sender)
-            .add_attribute("amount", amount);
+            .add_events(activated)
+            .add_events(fee_exempt_event(exempt, &info.sender))
+            .add_attribute("method", "send_split")
+            .add_attribute("sender", &info.sender)
+            .add_attribute("recipients", weighted.len().to_string());
 
         Ok(res)
     }
 
-    pub fn withdraw_all(
+    pub fn receive(
         deps: DepsMut,
+        env: Env,
         info: MessageInfo,
-        denom: String,
+        wrapper: Cw20ReceiveMsg,
     ) -> Result<Response, ContractError> {
-        let balance = BALANCES
-            .may_load(deps.storage, (&info.sender, denom.clone()))?
-            .unwrap_or_default();
-
-        withdraw(deps, info, balance, denom)
-    }
+        // Native coins attached to the call would be dropped along with info and left uncredited
+        nonpayable(&info)?;
 
-}
+        // The token contract calling receive is the CW20 asset that was sent, and the original sender of
+        // the tokens is the one sending them through the contract
+        let asset = AssetInfo::Cw20(info.sender);
+        let info = MessageInfo {
+            sender: deps.api.addr_validate(&wrapper.sender)?,
+            funds: vec![Coin {
+                denom: asset.denom(),
+                amount: wrapper.amount,
+            }],
+        };
 
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::GetOwner {} => to_binary(&query::owner(deps)?),
-        QueryMsg::GetFees {} => to_binary(&query::fees(deps)?),
-        QueryMsg::GetBalance { account, denom } => {
-            to_binary(&query::balance(deps, account, denom)?)
+        match from_binary(&wrapper.msg)? {
+            ReceiveMsg::Send {
+                account1,
+                account2,
+                referrer,
+            } => send(deps, env, info, account1, account2, referrer),
+            ReceiveMsg::SendSplit { recipients } => send_split(deps, env, info, recipients),
+            ReceiveMsg::SendEscrow {
+                recipients,
+                expires,
+            } => send_escrow(deps, env, info, recipients, expires),
+            ReceiveMsg::SendWithArbiter {
+                recipient,
+                arbiter,
+                expires,
+            } => send_with_arbiter(deps, env, info, recipient, arbiter, expires),
+            ReceiveMsg::SendVesting {
+                account1,
+                account2,
+                start,
+                cliff,
+                end,
+            } => send_vesting(deps, env, info, account1, account2, start, cliff, end),
         }
     }
-}
-
-pub mod query {
-    use cosmwasm_std::Addr;
-
-    use crate::{
-        msg::{GetBalanceResponse, GetFeesResponse, GetOwnerResponse},
-        state::BALANCES,
-    };
-
-    use super::*;
 
-    pub fn owner(deps: Deps) -> StdResult<GetOwnerResponse> {
-        let state = STATE.load(deps.storage)?;
-        Ok(GetOwnerResponse { owner: state.owner })
-    }
+    /// Sends funds evenly between two accounts like send, but locks each share behind a vesting schedule
+    /// so that it can only be withdrawn as it vests.
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_vesting(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        account1: String,
+        account2: String,
+        start: Timestamp,
+        cliff: Timestamp,
+        end: Timestamp,
+    ) -> Result<Response, ContractError> {
+        let address1: Addr = deps.api.addr_validate(&account1)?;
+        let address2: Addr = deps.api.addr_validate(&account2)?;
+        if address1 == address2 {
+            return Err(ContractError::DuplicateRecipientError {
+                address: address2.to_string(),
+            });
+        }
+        validate_send_funds(&info.funds)?;
+        if start > cliff || cliff > end || start >= end {
+            return Err(ContractError::InvalidVestingScheduleError {});
+        }
 
-    pub fn fees(deps: Deps) -> StdResult<GetFeesResponse> {
-        let state = STATE.load(deps.storage)?;
-        Ok(GetFeesResponse { fees: state.fees })
-    }
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        ensure_not_paused(state.deposits_paused, "deposits")?;
+        ensure_supported_funds(deps.storage, &state, &info.funds)?;
 
-    pub fn balance(deps: Deps, account: String, denom: String) -> StdResult<GetBalanceResponse> {
-        let address: Addr = deps.api.addr_validate(&account)?;
+        // The shares are credited to the balances right away, so they count towards the liabilities, and a
+        // schedule is added to lock each of them until it vests
+        let exempt: bool = FEE_EXEMPT.has(deps.storage, &info.sender);
+        let recipients = vec![(address1.clone(), 1), (address2.clone(), 1)];
+        for coin in info.funds.iter() {
+            let left_over: Uint128 = take_fees(deps.storage, &state, coin, exempt, None)?;
+            let shares = split_amount(left_over, &recipients);
+            for ((address, _), share) in recipients.iter().zip(shares) {
+                if share.is_zero() {
+                    continue;
+                }
+                let schedule = VestingSchedule {
+                    sender: info.sender.clone(),
+                    total: share,
+                    start,
+                    cliff,
+                    end,
+                };
+                add_vesting_schedule(deps.storage, &env, address, &coin.denom, schedule)?;
+                credit(deps.storage, address, share, &coin.denom)?;
+            }
+        }
 
-        let balance = BALANCES
-            .may_load(deps.storage, (&address, denom))?
-            .unwrap_or_default();
+        let res = Response::new()
+            .add_events(activated)
+            .add_events(fee_exempt_event(exempt, &info.sender))
+            .add_attribute("method", "send_vesting")
+            .add_attribute("sender", &info.sender)
+            .add_attribute("address_1", &address1)
+            .add_attribute("address_2", &address2)
+            .add_attribute("start", start.to_string())
+            .add_attribute("cliff", cliff.to_string())
+            .add_attribute("end", end.to_string());
 
-        Ok(GetBalanceResponse { balance: balance })
+        Ok(res)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::msg::{GetBalanceResponse, GetFeesResponse, GetOwnerResponse};
+    pub fn send_escrow(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        recipients: Vec<Recipient>,
+        expires: Timestamp,
+    ) -> Result<Response, ContractError> {
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        ensure_not_paused(state.deposits_paused, "deposits")?;
 
-    use super::*;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{coins, from_binary, BankMsg, CosmosMsg, Uint128, Addr};
+        if recipients.is_empty() {
+            return Err(ContractError::NoRecipientsError {});
+        }
+        validate_send_funds(&info.funds)?;
+        if expires <= env.block.time {
+            return Err(ContractError::InvalidExpirationError {
+                expires: Expiration::AtTime(expires),
+            });
+        }
 
-    #[test]
-    fn initialization_basic() {
-        let mut deps = mock_dependencies();
+        // Validating every recipient address and weight
+        let weighted = validate_recipients(deps.api, recipients, state.max_recipients)?;
+        ensure_supported_funds(deps.storage, &state, &info.funds)?;
 
-        let msg = InstantiateMsg { fees: 10 };
-        let info = mock_info("creator", &coins(0, "usei"));
+        // The fees are taken up front and the remainder of each coin is split into the escrowed shares, which
+        // are still owed to users until they are claimed or refunded
+        let exempt: bool = FEE_EXEMPT.has(deps.storage, &info.sender);
+        let mut shares: Vec<EscrowShare> = weighted
+            .iter()
+            .map(|(address, _)| EscrowShare {
+                address: address.clone(),
+                funds: vec![],
+            })
+            .collect();
+        for coin in info.funds.iter() {
+            let left_over: Uint128 = take_fees(deps.storage, &state, coin, exempt, None)?;
+            add_liability(deps.storage, left_over, &coin.denom)?;
+            for (share, amount) in shares.iter_mut().zip(split_amount(left_over, &weighted)) {
+                if !amount.is_zero() {
+                    share.funds.push(Coin {
+                        denom: coin.denom.clone(),
+                        amount,
+                    });
+                }
+            }
+        }
+        shares.retain(|share| !share.funds.is_empty());
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(0, res.messages.len());
+        let id: u64 = ESCROW_COUNT.may_load(deps.storage)?.unwrap_or_default();
+        ESCROW_COUNT.save(deps.storage, &(id + 1))?;
+        ESCROWS_BY_SENDER.save(deps.storage, (&info.sender, id), &Empty {})?;
+        for share in shares.iter() {
+            ESCROWS_BY_RECIPIENT.save(deps.storage, (&share.address, id), &Empty {})?;
+        }
+        let escrow = Escrow {
+            sender: info.sender.clone(),
+            shares,
+            expires,
+        };
+        ESCROWS.save(deps.storage, id, &escrow)?;
 
-        // it worked, let's query the state
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwner {}).unwrap();
-        let value: GetOwnerResponse = from_binary(&res).unwrap();
-        assert_eq!("creator", value.owner);
+        let res = Response::new()
+            .add_events(activated)
+            .add_events(fee_exempt_event(exempt, &info.sender))
+            .add_attribute("method", "send_escrow")
+            .add_attribute("sender", &info.sender)
+            .add_attribute("escrow_id", id.to_string())
+            .add_attribute("expires", expires.to_string());
 
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFees {}).unwrap();
-        let value: GetFeesResponse = from_binary(&res).unwrap();
-        assert_eq!(10, value.fees);
+        Ok(res)
     }
 
-    #[test]
-    fn initialization_fail() {
-        let mut deps = mock_dependencies();
+    pub fn claim(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let mut escrow: Escrow = ESCROWS.load(deps.storage, id)?;
+        if escrow.expires <= env.block.time {
+            return Err(ContractError::EscrowExpiredError {
+                id,
+                expires: escrow.expires,
+            });
+        }
 
-        let msg = InstantiateMsg { fees: 101 };
-        let info = mock_info("creator", &coins(0, "usei"));
+        let index = escrow
+            .shares
+            .iter()
+            .position(|share| share.address == info.sender)
+            .ok_or(ContractError::NothingToClaimError { id })?;
+        let share = escrow.shares.remove(index);
 
-        // we can just call .unwrap() to assert this was a success
-        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap_err();
-        match res {
-            ContractError::InvalidFeePercentageError { fees: _ } => (),
-            e => panic!("unexpected error: {:?}", e),
+        // The share moves from the escrow into the balance of the recipient
+        for coin in share.funds.iter() {
+            remove_liability(deps.storage, coin.amount, &coin.denom)?;
+            credit(deps.storage, &info.sender, coin.amount, &coin.denom)?;
+        }
+        ESCROWS_BY_RECIPIENT.remove(deps.storage, (&info.sender, id));
+        if escrow.shares.is_empty() {
+            ESCROWS.remove(deps.storage, id);
+            ESCROWS_BY_SENDER.remove(deps.storage, (&escrow.sender, id));
+        } else {
+            ESCROWS.save(deps.storage, id, &escrow)?;
         }
-    }
-
-    #[test]
-    fn send_basic() {
-        let mut deps = mock_dependencies();
 
-        let msg = InstantiateMsg { fees: 10 };
+        let res = Response::new()
+            .add_attribute("method", "claim")
+            .add_attribute("recipient", &info.sender)
+            .add_attribute("escrow_id", id.to_string());
 
-        // instantiate the contract
-        let info = mock_info("creator", &coins(0, "usei"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg);
+        Ok(res)
+    }
+
+    pub fn refund(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let escrow: Escrow = ESCROWS.load(deps.storage, id)?;
+        if info.sender != escrow.sender {
+            return Err(ContractError::UnauthorizedError {});
+        }
+        if env.block.time < escrow.expires {
+            return Err(ContractError::EscrowNotExpiredError {
+                id,
+                expires: escrow.expires,
+            });
+        }
+
+        // Every unclaimed share moves back into the balance of the sender
+        for share in escrow.shares.iter() {
+            for coin in share.funds.iter() {
+                remove_liability(deps.storage, coin.amount, &coin.denom)?;
+                credit(deps.storage, &escrow.sender, coin.amount, &coin.denom)?;
+            }
+            ESCROWS_BY_RECIPIENT.remove(deps.storage, (&share.address, id));
+        }
+        ESCROWS.remove(deps.storage, id);
+        ESCROWS_BY_SENDER.remove(deps.storage, (&escrow.sender, id));
+
+        let res = Response::new()
+            .add_attribute("method", "refund")
+            .add_attribute("sender", &info.sender)
+            .add_attribute("escrow_id", id.to_string());
+
+        Ok(res)
+    }
+
+    pub fn send_with_arbiter(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        recipient: String,
+        arbiter: String,
+        expires: Timestamp,
+    ) -> Result<Response, ContractError> {
+        let recipient: Addr = deps.api.addr_validate(&recipient)?;
+        let arbiter: Addr = deps.api.addr_validate(&arbiter)?;
+        if arbiter == recipient || arbiter == info.sender {
+            return Err(ContractError::InvalidArbiterError {
+                arbiter: arbiter.to_string(),
+            });
+        }
+
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        ensure_not_paused(state.deposits_paused, "deposits")?;
+
+        validate_send_funds(&info.funds)?;
+        if expires <= env.block.time {
+            return Err(ContractError::InvalidExpirationError {
+                expires: Expiration::AtTime(expires),
+            });
+        }
+        ensure_supported_funds(deps.storage, &state, &info.funds)?;
+
+        // The fees are only taken on approval, but a payment that could never cover them is rejected up front
+        for coin in info.funds.iter() {
+            fee_amount(deps.storage, &state, coin.amount, &coin.denom)?;
+            add_liability(deps.storage, coin.amount, &coin.denom)?;
+        }
+
+        let id: u64 = ARBITER_PAYMENT_COUNT.may_load(deps.storage)?.unwrap_or_default();
+        ARBITER_PAYMENT_COUNT.save(deps.storage, &(id + 1))?;
+        let payment = ArbiterPayment {
+            sender: info.sender.clone(),
+            recipient,
+            arbiter,
+            funds: info.funds,
+            expires,
+        };
+        ARBITER_PAYMENTS.save(deps.storage, id, &payment)?;
+
+        let res = Response::new()
+            .add_events(activated)
+            .add_attribute("method", "send_with_arbiter")
+            .add_attribute("sender", &payment.sender)
+            .add_attribute("recipient", &payment.recipient)
+            .add_attribute("arbiter", &payment.arbiter)
+            .add_attribute("payment_id", id.to_string())
+            .add_attribute("expires", expires.to_string());
+
+        Ok(res)
+    }
+
+    pub fn approve(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        id: u64,
+    ) -> Result<Response, ContractError> {
+        let payment: ArbiterPayment = ARBITER_PAYMENTS.load(deps.storage, id)?;
+        if info.sender != payment.arbiter {
+            return Err(ContractError::UnauthorizedError {});
+        }
+        if payment.expires <= env.block.time {
+            return Err(ContractError::PaymentExpiredError {
+                id,
+                expires: payment.expires,
+            });
+        }
+
+        // The payment leaves the pending funds and is charged the fees of a send from the sender
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        let exempt: bool = FEE_EXEMPT.has(deps.storage, &payment.sender);
+        for coin in payment.funds.iter() {
+            remove_liability(deps.storage, coin.amount, &coin.denom)?;
+            let left_over: Uint128 = take_fees(deps.storage, &state, coin, exempt, None)?;
+            credit(deps.storage, &payment.recipient, left_over, &coin.denom)?;
+        }
+        ARBITER_PAYMENTS.remove(deps.storage, id);
+
+        let res = Response::new()
+            .add_events(activated)
+            .add_events(fee_exempt_event(exempt, &payment.sender))
+            .add_attribute("method", "approve")
+            .add_attribute("arbiter", &info.sender)
+            .add_attribute("recipient", &payment.recipient)
+            .add_attribute("payment_id", id.to_string());
+
+        Ok(res)
+    }
+
+    pub fn reject(deps: DepsMut, info: MessageInfo, id: u64) -> Result<Response, ContractError> {
+        let payment: ArbiterPayment = ARBITER_PAYMENTS.load(deps.storage, id)?;
+        if info.sender != payment.arbiter {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        refund_arbiter_payment(deps.storage, id, &payment)?;
+
+        let res = Response::new()
+            .add_attribute("method", "reject")
+            .add_attribute("arbiter", &info.sender)
+            .add_attribute("sender", &payment.sender)
+            .add_attribute("payment_id", id.to_string());
+
+        Ok(res)
+    }
+
+    pub fn refund_payment(deps: DepsMut, env: Env, id: u64) -> Result<Response, ContractError> {
+        let payment: ArbiterPayment = ARBITER_PAYMENTS.load(deps.storage, id)?;
+        if env.block.time < payment.expires {
+            return Err(ContractError::PaymentNotExpiredError {
+                id,
+                expires: payment.expires,
+            });
+        }
+
+        refund_arbiter_payment(deps.storage, id, &payment)?;
+
+        let res = Response::new()
+            .add_attribute("method", "refund_payment")
+            .add_attribute("sender", &payment.sender)
+            .add_attribute("payment_id", id.to_string());
+
+        Ok(res)
+    }
+
+    /// Moves the funds of a pending payment back into the balance of the sender, free of fees, and removes
+    /// the payment.
+    fn refund_arbiter_payment(
+        storage: &mut dyn Storage,
+        id: u64,
+        payment: &ArbiterPayment,
+    ) -> Result<(), ContractError> {
+        for coin in payment.funds.iter() {
+            remove_liability(storage, coin.amount, &coin.denom)?;
+            credit(storage, &payment.sender, coin.amount, &coin.denom)?;
+        }
+        ARBITER_PAYMENTS.remove(storage, id);
+        Ok(())
+    }
+
+    /// Sends from fee exempt senders are marked with an event so that they can be told apart from sends
+    /// that were charged fees.
+    fn fee_exempt_event(exempt: bool, sender: &Addr) -> Option<Event> {
+        exempt.then(|| Event::new("fee_exempt_send").add_attribute("sender", sender))
+    }
+
+    /// Divides an amount between the recipients in proportion to their weights. Each share is rounded down
+    /// and the leftover dust, which is always less than the number of recipients, is handed out one unit at
+    /// a time starting from the last recipient.
+    fn split_amount(amount: Uint128, recipients: &[(Addr, u64)]) -> Vec<Uint128> {
+        let total_weight: Uint128 = recipients
+            .iter()
+            .map(|(_, weight)| Uint128::from(*weight))
+            .sum();
+        let shares: Vec<Uint128> = recipients
+            .iter()
+            .map(|(_, weight)| amount.multiply_ratio(*weight, total_weight))
+            .collect();
+        let dust: u128 = (amount - shares.iter().sum::<Uint128>()).u128();
+
+        shares
+            .into_iter()
+            .enumerate()
+            .map(|(index, share)| {
+                if ((recipients.len() - index) as u128) <= dust {
+                    share + Uint128::one()
+                } else {
+                    share
+                }
+            })
+            .collect()
+    }
+
+    /// Credits the fees taken from an amount to the fee recipients.
+    fn credit_fees(
+        storage: &mut dyn Storage,
+        state: &State,
+        fees: Uint128,
+        denom: &str,
+    ) -> Result<(), ContractError> {
+        let recipients = fee_recipients(state);
+        for ((address, _), share) in recipients.iter().zip(split_amount(fees, &recipients)) {
+            credit(storage, address, share, denom)?;
+        }
+        Ok(())
+    }
+
+    /// Takes the fees owed on a coin and returns the amount left over for the recipients. No fees are taken
+    /// when the sender is fee exempt, and the referral share of the fees is credited to the referrer if
+    /// there is one.
+    fn take_fees(
+        storage: &mut dyn Storage,
+        state: &State,
+        coin: &Coin,
+        exempt: bool,
+        referrer: Option<&Addr>,
+    ) -> Result<Uint128, ContractError> {
+        // Updating the fee recipients balances. The fee is rounded down, so without a minimum fee sends
+        // too small to owe a whole unit of fees (amount * fees < 10_000) are not charged at all
+        let owner_fees: Uint128 = if exempt {
+            Uint128::zero()
+        } else {
+            fee_amount(storage, state, coin.amount, &coin.denom)?
+        };
+
+        // The referral share of the fees is rounded down so the fee recipients keep any dust
+        let referral_fees: Uint128 = match referrer {
+            Some(referrer) => {
+                let referral_fees = owner_fees.multiply_ratio(state.referral_share, BPS_DENOMINATOR);
+                credit(storage, referrer, referral_fees, &coin.denom)?;
+                if !referral_fees.is_zero() {
+                    REFERRAL_EARNINGS.update(
+                        storage,
+                        (referrer, coin.denom.clone()),
+                        |earnings: Option<Uint128>| -> StdResult<_> {
+                            Ok(earnings.unwrap_or_default() + referral_fees)
+                        },
+                    )?;
+                }
+                referral_fees
+            }
+            None => Uint128::zero(),
+        };
+        credit_fees(storage, state, owner_fees - referral_fees, &coin.denom)?;
+
+        Ok(coin.amount - owner_fees)
+    }
+
+    /// Credits the fees for every coin to the fee recipients and divides the remainder between the
+    /// recipients in proportion to their weights.
+    fn distribute_funds(
+        storage: &mut dyn Storage,
+        state: &State,
+        funds: &[Coin],
+        recipients: &[(Addr, u64)],
+        exempt: bool,
+        referrer: Option<&Addr>,
+    ) -> Result<(), ContractError> {
+        ensure_supported_funds(storage, state, funds)?;
+
+        // Iterating through all of the coins for distribution
+        for coin in funds.iter() {
+            let left_over: Uint128 = take_fees(storage, state, coin, exempt, referrer)?;
+
+            // Updating the remaining balances
+            for ((address, _), share) in recipients.iter().zip(split_amount(left_over, recipients)) {
+                credit(storage, address, share, &coin.denom)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn withdraw(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        amount: Uint128,
+        denom: String,
+        recipient: Option<String>,
+        msg: Option<Binary>,
+    ) -> Result<Response, ContractError> {
+        let state: State = STATE.load(deps.storage)?;
+        ensure_not_paused(state.withdrawals_paused, "withdrawals")?;
+
+        if amount.is_zero() {
+            return Err(ContractError::ZeroAmountError { denom });
+        }
+
+        // Funds are paid out to the sender unless another recipient is given
+        let recipient: Addr = match recipient {
+            Some(recipient) => deps.api.addr_validate(&recipient)?,
+            None => info.sender.clone(),
+        };
+
+        debit(deps.storage, &env, &info.sender, amount, &denom)?;
+
+        let res = Response::new()
+            .add_message(transfer_msg(&recipient, amount, &denom, msg)?)
+            .add_attribute("withdraw", &info.sender)
+            .add_attribute("recipient", &recipient)
+            .add_attribute("amount", amount);
+
+        Ok(res)
+    }
+
+    pub fn withdraw_all(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        denom: String,
+        recipient: Option<String>,
+        msg: Option<Binary>,
+    ) -> Result<Response, ContractError> {
+        // Only the vested part of the balance can be withdrawn
+        let balance = withdrawable_amount(deps.storage, &info.sender, &denom, env.block.time)?;
+
+        withdraw(deps, env, info, balance, denom, recipient, msg)
+    }
+
+    pub fn withdraw_many(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        coins: Vec<Coin>,
+    ) -> Result<Response, ContractError> {
+        let state: State = STATE.load(deps.storage)?;
+        ensure_not_paused(state.withdrawals_paused, "withdrawals")?;
+        if coins.is_empty() {
+            return Err(ContractError::NothingToWithdrawError {});
+        }
+        if let Some(coin) = coins.iter().find(|coin| coin.amount.is_zero()) {
+            return Err(ContractError::ZeroAmountError {
+                denom: coin.denom.clone(),
+            });
+        }
+
+        // Merging repeated denoms so each balance is checked against the full amount requested
+        let mut requested: Vec<Coin> = vec![];
+        for coin in coins {
+            match requested.iter_mut().find(|c| c.denom == coin.denom) {
+                Some(existing) => {
+                    existing.amount = existing
+                        .amount
+                        .checked_add(coin.amount)
+                        .map_err(StdError::from)?
+                }
+                None => requested.push(coin),
+            }
+        }
+
+        // Any insufficient balance fails the whole message before anything is debited
+        for coin in requested.iter() {
+            ensure_withdrawable(
+                deps.storage,
+                &info.sender,
+                coin.amount,
+                &coin.denom,
+                env.block.time,
+            )?;
+        }
+
+        for coin in requested.iter() {
+            debit(deps.storage, &env, &info.sender, coin.amount, &coin.denom)?;
+        }
+
+        let res = Response::new()
+            .add_messages(withdraw_msgs(&info.sender, requested)?)
+            .add_attribute("method", "withdraw_many")
+            .add_attribute("withdraw", &info.sender);
+
+        Ok(res)
+    }
+
+    pub fn withdraw_everything(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        // Only the vested part of each balance can be withdrawn, and denoms with nothing vested are skipped
+        let denoms = BALANCES
+            .prefix(&info.sender)
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        let mut coins: Vec<Coin> = Vec::with_capacity(denoms.len());
+        for denom in denoms {
+            let amount = withdrawable_amount(deps.storage, &info.sender, &denom, env.block.time)?;
+            if !amount.is_zero() {
+                coins.push(Coin { denom, amount });
+            }
+        }
+
+        withdraw_many(deps, env, info, coins)
+    }
+
+    pub fn transfer_balance(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        to: String,
+        amount: Uint128,
+        denom: String,
+    ) -> Result<Response, ContractError> {
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        ensure_not_paused(state.withdrawals_paused, "withdrawals")?;
+
+        if amount.is_zero() {
+            return Err(ContractError::ZeroAmountError { denom });
+        }
+
+        let to: Addr = deps.api.addr_validate(&to)?;
+
+        // The credit moves within the contract so no funds are sent. The send fee is only charged on
+        // transfers if the contract is configured to
+        let owner_fees: Uint128 = if state.charge_transfer_fees {
+            fee_amount(deps.storage, &state, amount, &denom)?
+        } else {
+            Uint128::zero()
+        };
+
+        debit(deps.storage, &env, &info.sender, amount, &denom)?;
+        credit_fees(deps.storage, &state, owner_fees, &denom)?;
+        credit(deps.storage, &to, amount - owner_fees, &denom)?;
+
+        let res = Response::new()
+            .add_events(activated)
+            .add_attribute("method", "transfer_balance")
+            .add_attribute("from", &info.sender)
+            .add_attribute("to", &to)
+            .add_attribute("denom", denom)
+            .add_attribute("amount", amount)
+            .add_attribute("fees", owner_fees);
+
+        Ok(res)
+    }
+
+    /// Increases (or decreases) the allowance of a spender, optionally replacing its expiry. Allowances
+    /// decreased to zero are removed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_allowance(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        spender: String,
+        denom: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+        increase: bool,
+    ) -> Result<Response, ContractError> {
+        let spender: Addr = deps.api.addr_validate(&spender)?;
+        if spender == info.sender {
+            return Err(ContractError::CannotSetOwnAllowanceError {});
+        }
+
+        // An allowance that is already expired could never be spent
+        if let Some(expires) = expires {
+            if expires.is_expired(&env.block) {
+                return Err(ContractError::InvalidExpirationError { expires });
+            }
+        }
+
+        let key = (&info.sender, &spender, denom.clone());
+        let mut allowance = ALLOWANCES.may_load(deps.storage, key.clone())?.unwrap_or_default();
+        if increase {
+            allowance.amount = allowance.amount.checked_add(amount).map_err(StdError::from)?;
+        } else {
+            allowance.amount = allowance.amount.saturating_sub(amount);
+        }
+        if let Some(expires) = expires {
+            allowance.expires = expires;
+        }
+
+        if allowance.amount.is_zero() {
+            ALLOWANCES.remove(deps.storage, key);
+        } else {
+            ALLOWANCES.save(deps.storage, key, &allowance)?;
+        }
+
+        let res = Response::new()
+            .add_attribute(
+                "method",
+                if increase { "increase_allowance" } else { "decrease_allowance" },
+            )
+            .add_attribute("owner", &info.sender)
+            .add_attribute("spender", &spender)
+            .add_attribute("denom", denom)
+            .add_attribute("amount", allowance.amount)
+            .add_attribute("expires", allowance.expires.to_string());
+
+        Ok(res)
+    }
+
+    pub fn withdraw_from(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        owner: String,
+        amount: Uint128,
+        denom: String,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let state: State = STATE.load(deps.storage)?;
+        ensure_not_paused(state.withdrawals_paused, "withdrawals")?;
+
+        let owner: Addr = deps.api.addr_validate(&owner)?;
+        if amount.is_zero() {
+            return Err(ContractError::ZeroAmountError { denom });
+        }
+
+        // Funds are paid out to the spender unless another recipient is given
+        let recipient: Addr = match recipient {
+            Some(recipient) => deps.api.addr_validate(&recipient)?,
+            None => info.sender.clone(),
+        };
+
+        // Spending the allowance before debiting the balance of the owner
+        let key = (&owner, &info.sender, denom.clone());
+        let mut allowance = ALLOWANCES.may_load(deps.storage, key.clone())?.unwrap_or_default();
+        if allowance.expires.is_expired(&env.block) {
+            return Err(ContractError::AllowanceExpiredError {
+                denom,
+                expires: allowance.expires,
+            });
+        }
+        if amount > allowance.amount {
+            return Err(ContractError::InsufficientAllowanceError {
+                denom,
+                allowance: allowance.amount,
+                requested: amount,
+            });
+        }
+        allowance.amount -= amount;
+        if allowance.amount.is_zero() {
+            ALLOWANCES.remove(deps.storage, key);
+        } else {
+            ALLOWANCES.save(deps.storage, key, &allowance)?;
+        }
+
+        debit(deps.storage, &env, &owner, amount, &denom)?;
+
+        let res = Response::new()
+            .add_message(transfer_msg(&recipient, amount, &denom, None)?)
+            .add_attribute("method", "withdraw_from")
+            .add_attribute("withdraw", &owner)
+            .add_attribute("spender", &info.sender)
+            .add_attribute("recipient", &recipient)
+            .add_attribute("amount", amount);
+
+        Ok(res)
+    }
+
+    /// Credits an amount of a denom to the balance of an account and to the total liabilities. Zero amounts
+    /// are skipped so no zero balances are stored.
+    fn credit(
+        storage: &mut dyn Storage,
+        address: &Addr,
+        amount: Uint128,
+        denom: &str,
+    ) -> Result<(), ContractError> {
+        if amount.is_zero() {
+            return Ok(());
+        }
+
+        BALANCES.update(
+            storage,
+            (address, denom.to_owned()),
+            |balance: Option<Uint128>| -> StdResult<_> {
+                Ok(balance.unwrap_or_default() + amount)
+            },
+        )?;
+        add_liability(storage, amount, denom)?;
+
+        Ok(())
+    }
+
+    /// Locks part of the balance of a denom of an account behind a new vesting schedule. Every schedule is read
+    /// whenever the account withdraws the denom, so fully vested schedules are pruned first and each sender can
+    /// only lock the balance with a capped number of schedules, which keeps one sender from using up the slots
+    /// of another. Registered denoms also require each schedule to lock at least their minimum send amount so
+    /// that filling up schedules with dust is costly.
+    fn add_vesting_schedule(
+        storage: &mut dyn Storage,
+        env: &Env,
+        address: &Addr,
+        denom: &str,
+        schedule: VestingSchedule,
+    ) -> Result<(), ContractError> {
+        if let Some(min_send) = SUPPORTED_DENOMS
+            .may_load(storage, denom.to_owned())?
+            .and_then(|info| info.min_send)
+        {
+            if schedule.total < min_send {
+                return Err(ContractError::BelowMinimumSendError {
+                    denom: denom.to_owned(),
+                    amount: schedule.total,
+                    min_send,
+                });
+            }
+        }
+
+        prune_vesting_schedules(storage, address, denom, env.block.time)?;
+        let count = vesting_schedules(storage, address, denom)?
+            .iter()
+            .filter(|(_, existing)| existing.sender == schedule.sender)
+            .count();
+        if count >= MAX_VESTING_SCHEDULES as usize {
+            return Err(ContractError::TooManyVestingSchedulesError {
+                address: address.to_string(),
+                denom: denom.to_owned(),
+                sender: schedule.sender.to_string(),
+                max: MAX_VESTING_SCHEDULES,
+            });
+        }
+
+        let id: u64 = VESTING_SCHEDULE_COUNT.may_load(storage)?.unwrap_or_default();
+        VESTING_SCHEDULE_COUNT.save(storage, &(id + 1))?;
+        VESTING_SCHEDULES.save(storage, (address, denom.to_owned(), id), &schedule)?;
+
+        Ok(())
+    }
+
+    /// Debits an amount of a denom from the balance of an account and from the total liabilities, failing if
+    /// the balance is insufficient or if part of the amount has not vested yet.
+    fn debit(
+        storage: &mut dyn Storage,
+        env: &Env,
+        address: &Addr,
+        amount: Uint128,
+        denom: &str,
+    ) -> Result<(), ContractError> {
+        ensure_withdrawable(storage, address, amount, denom, env.block.time)?;
+        let balance = BALANCES
+            .may_load(storage, (address, denom.to_owned()))?
+            .unwrap_or_default();
+
+        prune_vesting_schedules(storage, address, denom, env.block.time)?;
+
+        // Removing the entry once the balance is fully withdrawn so no zero balances are left behind
+        let remaining: Uint128 = balance - amount;
+        if remaining.is_zero() {
+            BALANCES.remove(storage, (address, denom.to_owned()));
+        } else {
+            BALANCES.save(storage, (address, denom.to_owned()), &remaining)?;
+        }
+        remove_liability(storage, amount, denom)?;
+
+        Ok(())
+    }
+
+    /// Adds an amount of a denom to the total owed to users, which covers both balances and escrowed funds.
+    fn add_liability(storage: &mut dyn Storage, amount: Uint128, denom: &str) -> StdResult<()> {
+        TOTAL_LIABILITIES.update(
+            storage,
+            denom.to_owned(),
+            |total: Option<Uint128>| -> StdResult<_> {
+                Ok(total.unwrap_or_default() + amount)
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Removes an amount of a denom from the total owed to users.
+    fn remove_liability(storage: &mut dyn Storage, amount: Uint128, denom: &str) -> StdResult<()> {
+        TOTAL_LIABILITIES.update(
+            storage,
+            denom.to_owned(),
+            |total: Option<Uint128>| -> StdResult<_> {
+                Ok(total.unwrap_or_default().checked_sub(amount)?)
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Builds the messages paying out several distinct coins at once. Native coins are sent together in a
+    /// single bank send while each CW20 token needs its own transfer. Zero amounts are left out since they
+    /// cannot be sent.
+    fn withdraw_msgs(recipient: &Addr, coins: Vec<Coin>) -> StdResult<Vec<CosmosMsg>> {
+        let mut native: Vec<Coin> = vec![];
+        let mut msgs: Vec<CosmosMsg> = vec![];
+
+        for coin in coins.into_iter().filter(|coin| !coin.amount.is_zero()) {
+            match AssetInfo::from_denom(&coin.denom) {
+                AssetInfo::Native(_) => native.push(coin),
+                AssetInfo::Cw20(_) => {
+                    msgs.push(transfer_msg(recipient, coin.amount, &coin.denom, None)?)
+                }
+            }
+        }
+
+        if !native.is_empty() {
+            msgs.insert(
+                0,
+                BankMsg::Send {
+                    to_address: recipient.to_string(),
+                    amount: native,
+                }
+                .into(),
+            );
+        }
+
+        Ok(msgs)
+    }
+
+    pub fn propose_new_owner(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        new_owner: String,
+        expires: Option<Expiration>,
+    ) -> Result<Response, ContractError> {
+        let state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        let new_owner: Addr = deps.api.addr_validate(&new_owner)?;
+
+        // A proposal that is already expired could never be accepted
+        if let Some(expires) = expires {
+            if expires.is_expired(&env.block) {
+                return Err(ContractError::OwnershipProposalExpiredError { expires });
+            }
+        }
+
+        PENDING_OWNER.save(
+            deps.storage,
+            &PendingOwner {
+                new_owner: new_owner.clone(),
+                expires,
+            },
+        )?;
+
+        let res = Response::new()
+            .add_attribute("method", "propose_new_owner")
+            .add_attribute("owner", &info.sender)
+            .add_attribute("pending_owner", &new_owner)
+            .add_attribute("expires", expires.unwrap_or_default().to_string());
+
+        Ok(res)
+    }
+
+    pub fn accept_ownership(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        let pending = PENDING_OWNER
+            .may_load(deps.storage)?
+            .ok_or(ContractError::NoPendingOwnerError {})?;
+
+        if info.sender != pending.new_owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        if let Some(expires) = pending.expires {
+            if expires.is_expired(&env.block) {
+                return Err(ContractError::OwnershipProposalExpiredError { expires });
+            }
+        }
+
+        // Fees already credited to the previous owner remain in their balances, only future fees
+        // will be credited to the new owner
+        let mut state: State = STATE.load(deps.storage)?;
+        let previous_owner: Addr = state.owner;
+        state.owner = pending.new_owner;
+        STATE.save(deps.storage, &state)?;
+        PENDING_OWNER.remove(deps.storage);
+
+        let res = Response::new()
+            .add_attribute("method", "accept_ownership")
+            .add_attribute("previous_owner", previous_owner)
+            .add_attribute("owner", &state.owner);
+
+        Ok(res)
+    }
+
+    pub fn cancel_ownership_proposal(
+        deps: DepsMut,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        let state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        if PENDING_OWNER.may_load(deps.storage)?.is_none() {
+            return Err(ContractError::NoPendingOwnerError {});
+        }
+        PENDING_OWNER.remove(deps.storage);
+
+        let res = Response::new()
+            .add_attribute("method", "cancel_ownership_proposal")
+            .add_attribute("owner", &info.sender);
+
+        Ok(res)
+    }
+
+    pub fn update_fees(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        fees: u16,
+    ) -> Result<Response, ContractError> {
+        // Activating any update that is already due so it is not silently replaced by the new one
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        validate_fees(fees)?;
+
+        // Queueing the new fees, replacing any update that has not activated yet
+        let activates_at = env.block.time.plus_seconds(state.fee_update_delay);
+        PENDING_CONFIG.save(deps.storage, &PendingConfig { fees, activates_at })?;
+
+        let res = Response::new()
+            .add_events(activated)
+            .add_event(
+                Event::new("fee_update_scheduled")
+                    .add_attribute("current_fees", state.fees.to_string())
+                    .add_attribute("fees", fees.to_string())
+                    .add_attribute("activates_at", activates_at.to_string()),
+            )
+            .add_attribute("method", "update_fees")
+            .add_attribute("owner", &info.sender);
+
+        Ok(res)
+    }
+
+    pub fn update_fee_recipients(
+        deps: DepsMut,
+        info: MessageInfo,
+        recipients: Vec<Recipient>,
+    ) -> Result<Response, ContractError> {
+        let mut state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        state.fee_recipients = validate_recipients(deps.api, recipients, state.max_recipients)?
+            .into_iter()
+            .map(|(address, weight)| FeeRecipient { address, weight })
+            .collect();
+        if state.fee_recipient.is_some() && !state.fee_recipients.is_empty() {
+            return Err(ContractError::ConflictingFeeRecipientsError {});
+        }
+        STATE.save(deps.storage, &state)?;
+
+        let res = Response::new()
+            .add_attribute("method", "update_fee_recipients")
+            .add_attribute("owner", &info.sender)
+            .add_attribute("recipients", state.fee_recipients.len().to_string());
+
+        Ok(res)
+    }
+
+    pub fn update_fee_recipient(
+        deps: DepsMut,
+        info: MessageInfo,
+        fee_recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let mut state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        state.fee_recipient = fee_recipient
+            .map(|fee_recipient| deps.api.addr_validate(&fee_recipient))
+            .transpose()?;
+        if state.fee_recipient.is_some() && !state.fee_recipients.is_empty() {
+            return Err(ContractError::ConflictingFeeRecipientsError {});
+        }
+        STATE.save(deps.storage, &state)?;
+
+        let res = Response::new()
+            .add_attribute("method", "update_fee_recipient")
+            .add_attribute("owner", &info.sender)
+            .add_attribute("fee_recipient", super::fee_recipient(&state));
+
+        Ok(res)
+    }
+
+    pub fn update_referral_share(
+        deps: DepsMut,
+        info: MessageInfo,
+        referral_share: u16,
+    ) -> Result<Response, ContractError> {
+        let mut state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        validate_referral_share(referral_share)?;
+        state.referral_share = referral_share;
+        STATE.save(deps.storage, &state)?;
+
+        let res = Response::new()
+            .add_attribute("method", "update_referral_share")
+            .add_attribute("owner", &info.sender)
+            .add_attribute("referral_share", referral_share.to_string());
+
+        Ok(res)
+    }
+
+    pub fn register_denom(
+        deps: DepsMut,
+        info: MessageInfo,
+        denom: String,
+        display_name: String,
+        decimals: u8,
+        min_send: Option<Uint128>,
+    ) -> Result<Response, ContractError> {
+        let state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        let denom_info = DenomInfo {
+            display_name,
+            decimals,
+            min_send,
+        };
+        SUPPORTED_DENOMS.save(deps.storage, denom.clone(), &denom_info)?;
+
+        let res = Response::new()
+            .add_attribute("method", "register_denom")
+            .add_attribute("owner", &info.sender)
+            .add_attribute("denom", denom);
+
+        Ok(res)
+    }
+
+    pub fn deregister_denom(
+        deps: DepsMut,
+        info: MessageInfo,
+        denom: String,
+    ) -> Result<Response, ContractError> {
+        let state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        SUPPORTED_DENOMS.remove(deps.storage, denom.clone());
+
+        let res = Response::new()
+            .add_attribute("method", "deregister_denom")
+            .add_attribute("owner", &info.sender)
+            .add_attribute("denom", denom);
+
+        Ok(res)
+    }
+
+    pub fn set_denom_allowlist(
+        deps: DepsMut,
+        info: MessageInfo,
+        enabled: bool,
+    ) -> Result<Response, ContractError> {
+        let mut state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        state.denom_allowlist = enabled;
+        STATE.save(deps.storage, &state)?;
+
+        let res = Response::new()
+            .add_attribute("method", "set_denom_allowlist")
+            .add_attribute("owner", &info.sender)
+            .add_attribute("denom_allowlist", enabled.to_string());
+
+        Ok(res)
+    }
+
+    /// Queues setting (or clearing, if fees is None) the fees of a denom behind the fee update delay, the
+    /// same way as UpdateFees, so that users get advance notice of the change.
+    pub fn queue_denom_fee(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        denom: String,
+        fees: Option<u16>,
+    ) -> Result<Response, ContractError> {
+        // Activating any update that is already due so it is not silently replaced by the new one
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        if let Some(fees) = fees {
+            validate_fees(fees)?;
+        }
+
+        // Queueing the change, replacing any change to the denom that has not activated yet
+        let activates_at = env.block.time.plus_seconds(state.fee_update_delay);
+        PENDING_DENOM_FEES.save(
+            deps.storage,
+            denom.clone(),
+            &PendingDenomFee { fees, activates_at },
+        )?;
+
+        let res = Response::new()
+            .add_events(activated)
+            .add_event(
+                Event::new("fee_update_scheduled")
+                    .add_attribute("denom", &denom)
+                    .add_attribute(
+                        "current_fees",
+                        fees_for_denom(deps.storage, &state, &denom)?.to_string(),
+                    )
+                    .add_attribute("fees", fees.unwrap_or(state.fees).to_string())
+                    .add_attribute("activates_at", activates_at.to_string()),
+            )
+            .add_attribute(
+                "method",
+                if fees.is_some() { "set_denom_fee" } else { "clear_denom_fee" },
+            )
+            .add_attribute("owner", &info.sender)
+            .add_attribute("denom", denom);
+
+        Ok(res)
+    }
+
+    /// Queues new bounds on the fee of a denom behind the fee update delay, the same way as UpdateFees, so
+    /// that a raised minimum or maximum fee does not take users by surprise.
+    pub fn set_fee_bounds(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        denom: String,
+        min_fee: Option<Uint128>,
+        max_fee: Option<Uint128>,
+    ) -> Result<Response, ContractError> {
+        // Activating any update that is already due so it is not silently replaced by the new one
+        let (state, activated) = load_active_state(deps.storage, &env)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        if let (Some(min_fee), Some(max_fee)) = (min_fee, max_fee) {
+            if min_fee > max_fee {
+                return Err(ContractError::InvalidFeeBoundsError { min_fee, max_fee });
+            }
+        }
+
+        // Queueing the bounds, replacing any bounds for the denom that have not activated yet
+        let activates_at = env.block.time.plus_seconds(state.fee_update_delay);
+        PENDING_FEE_BOUNDS.save(
+            deps.storage,
+            denom.clone(),
+            &PendingFeeBounds {
+                bounds: FeeBounds { min_fee, max_fee },
+                activates_at,
+            },
+        )?;
+
+        let res = Response::new()
+            .add_events(activated)
+            .add_event(
+                Event::new("fee_bounds_update_scheduled")
+                    .add_attribute("denom", &denom)
+                    .add_attribute("min_fee", optional_amount(min_fee))
+                    .add_attribute("max_fee", optional_amount(max_fee))
+                    .add_attribute("activates_at", activates_at.to_string()),
+            )
+            .add_attribute("method", "set_fee_bounds")
+            .add_attribute("owner", &info.sender)
+            .add_attribute("denom", denom)
+            .add_attribute("min_fee", optional_amount(min_fee))
+            .add_attribute("max_fee", optional_amount(max_fee));
+
+        Ok(res)
+    }
+
+    pub fn update_fee_exempt(
+        deps: DepsMut,
+        info: MessageInfo,
+        address: String,
+        exempt: bool,
+    ) -> Result<Response, ContractError> {
+        let state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        let address: Addr = deps.api.addr_validate(&address)?;
+        if exempt {
+            FEE_EXEMPT.save(deps.storage, &address, &Empty {})?;
+        } else {
+            FEE_EXEMPT.remove(deps.storage, &address);
+        }
+
+        let method = if exempt { "add_fee_exempt" } else { "remove_fee_exempt" };
+        let res = Response::new()
+            .add_attribute("method", method)
+            .add_attribute("owner", &info.sender)
+            .add_attribute("address", &address);
+
+        Ok(res)
+    }
+
+    pub fn sweep_surplus(
+        deps: DepsMut,
+        env: Env,
+        info: MessageInfo,
+        denom: String,
+        recipient: String,
+    ) -> Result<Response, ContractError> {
+        let state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        let recipient: Addr = deps.api.addr_validate(&recipient)?;
+
+        // Only funds beyond what is owed to users can be swept
+        let liabilities = TOTAL_LIABILITIES
+            .may_load(deps.storage, denom.clone())?
+            .unwrap_or_default();
+        let balance = query_asset_balance(&deps.querier, &env.contract.address, &denom)?;
+        let surplus = balance.saturating_sub(liabilities);
+        if surplus.is_zero() {
+            return Err(ContractError::NoSurplusError { denom });
+        }
+
+        let res = Response::new()
+            .add_message(transfer_msg(&recipient, surplus, &denom, None)?)
+            .add_attribute("method", "sweep_surplus")
+            .add_attribute("owner", &info.sender)
+            .add_attribute("recipient", &recipient)
+            .add_attribute("denom", denom)
+            .add_attribute("amount", surplus);
+
+        Ok(res)
+    }
+
+    /// Pauses (or unpauses) the selected actions, leaving the others as they are.
+    pub fn set_paused(
+        deps: DepsMut,
+        info: MessageInfo,
+        deposits: bool,
+        withdrawals: bool,
+        paused: bool,
+    ) -> Result<Response, ContractError> {
+        let mut state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner && Some(&info.sender) != state.pauser.as_ref() {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        if deposits {
+            state.deposits_paused = paused;
+        }
+        if withdrawals {
+            state.withdrawals_paused = paused;
+        }
+        STATE.save(deps.storage, &state)?;
+
+        let res = Response::new()
+            .add_attribute("method", if paused { "pause" } else { "unpause" })
+            .add_attribute("sender", &info.sender)
+            .add_attribute("deposits_paused", state.deposits_paused.to_string())
+            .add_attribute("withdrawals_paused", state.withdrawals_paused.to_string());
+
+        Ok(res)
+    }
+
+    pub fn update_pauser(
+        deps: DepsMut,
+        info: MessageInfo,
+        pauser: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let mut state: State = STATE.load(deps.storage)?;
+        if info.sender != state.owner {
+            return Err(ContractError::UnauthorizedError {});
+        }
+
+        state.pauser = pauser
+            .map(|pauser| deps.api.addr_validate(&pauser))
+            .transpose()?;
+        STATE.save(deps.storage, &state)?;
+
+        let res = Response::new()
+            .add_attribute("method", "update_pauser")
+            .add_attribute("owner", &info.sender)
+            .add_attribute(
+                "pauser",
+                state.pauser.map(String::from).unwrap_or_else(|| "none".to_owned()),
+            );
+
+        Ok(res)
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+
+    // Only instances of this contract can be migrated
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidContractNameError {
+            expected: CONTRACT_NAME.to_owned(),
+            actual: stored.contract,
+        });
+    }
+
+    let stored_version = migrate::parse_version(&stored.version)?;
+    let current_version = migrate::parse_version(CONTRACT_VERSION)?;
+    if stored_version > current_version {
+        return Err(ContractError::MigrationDowngradeError {
+            stored: stored.version,
+            current: CONTRACT_VERSION.to_owned(),
+        });
+    }
+
+    // Running every transform introduced after the stored version, oldest first
+    for (version, transform) in migrate::MIGRATIONS {
+        if stored_version < migrate::parse_version(version)? {
+            transform(deps.storage)?;
+        }
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let res = Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("previous_version", stored.version)
+        .add_attribute("version", CONTRACT_VERSION);
+
+    Ok(res)
+}
+
+pub mod migrate {
+    use cosmwasm_std::Order;
+    use semver::Version;
+
+    use crate::state::{BALANCES, STATE_V0_1_0, TOTAL_LIABILITIES};
+
+    use super::*;
+
+    /// A state transform that upgrades STATE and BALANCES to the layout of a given version.
+    pub type Migration = fn(&mut dyn Storage) -> Result<(), ContractError>;
+
+    /// Every state transform keyed by the version that introduced the new layout. Must be kept in
+    /// ascending version order since the transforms are applied in sequence.
+    pub const MIGRATIONS: &[(&str, Migration)] = &[("0.2.0", v0_2_0)];
+
+    pub fn parse_version(version: &str) -> Result<Version, ContractError> {
+        Version::parse(version).map_err(|_| ContractError::InvalidVersionError {
+            version: version.to_owned(),
+        })
+    }
+
+    /// Version 0.1.0 stored fees as a whole percentage and had no fee update delay, recipient limit, pause,
+    /// transfer fee, fee recipient, referral or denom allowlist settings. Fees are converted to basis points
+    /// and the new settings are set to their defaults: no weighted fee recipients, no fee recipient so the
+    /// owner collects the fees, no referral share and open mode for denoms. Zero balances left behind by
+    /// withdrawals are pruned from BALANCES and the total liabilities are computed from it.
+    pub fn v0_2_0(storage: &mut dyn Storage) -> Result<(), ContractError> {
+        let legacy = STATE_V0_1_0.load(storage)?;
+        let state = State {
+            owner: legacy.owner,
+            fees: u16::from(legacy.fees) * 100,
+            fee_update_delay: DEFAULT_FEE_UPDATE_DELAY,
+            max_recipients: DEFAULT_MAX_RECIPIENTS,
+            pauser: None,
+            deposits_paused: false,
+            withdrawals_paused: false,
+            charge_transfer_fees: false,
+            fee_recipients: vec![],
+            fee_recipient: None,
+            referral_share: 0,
+            denom_allowlist: false,
+        };
+        STATE.save(storage, &state)?;
+
+        let zero_balances = BALANCES
+            .range(storage, None, None, Order::Ascending)
+            .filter_map(|item| match item {
+                Ok((key, amount)) if amount.is_zero() => Some(Ok(key)),
+                Ok(_) => None,
+                Err(e) => Some(Err(e)),
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+        for (address, denom) in zero_balances {
+            BALANCES.remove(storage, (&address, denom));
+        }
+
+        // Totalling the liabilities of every denom from the existing balances
+        let balances = BALANCES
+            .range(storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for ((_, denom), amount) in balances {
+            TOTAL_LIABILITIES.update(storage, denom, |total: Option<Uint128>| -> StdResult<_> {
+                Ok(total.unwrap_or_default() + amount)
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::GetOwner {} => to_binary(&query::owner(deps)?),
+        QueryMsg::GetFees {} => to_binary(&query::fees(deps, env)?),
+        QueryMsg::GetConfig {} => to_binary(&query::config(deps)?),
+        QueryMsg::ListSupportedDenoms { start_after, limit } => {
+            to_binary(&query::list_supported_denoms(deps, start_after, limit)?)
+        }
+        QueryMsg::GetArbiterPayment { id } => to_binary(&query::arbiter_payment(deps, id)?),
+        QueryMsg::GetEscrow { id } => to_binary(&query::escrow(deps, id)?),
+        QueryMsg::ListEscrowsBySender {
+            sender,
+            start_after,
+            limit,
+        } => to_binary(&query::list_escrows(
+            deps,
+            ESCROWS_BY_SENDER,
+            sender,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::ListEscrowsByRecipient {
+            recipient,
+            start_after,
+            limit,
+        } => to_binary(&query::list_escrows(
+            deps,
+            ESCROWS_BY_RECIPIENT,
+            recipient,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::GetReferralStats { referrer } => {
+            to_binary(&query::referral_stats(deps, referrer)?)
+        }
+        QueryMsg::GetFeeRecipients {} => to_binary(&query::fee_recipients(deps)?),
+        QueryMsg::GetFeeForDenom { denom } => to_binary(&query::fee_for_denom(deps, env, denom)?),
+        QueryMsg::ListFeeExempt { start_after, limit } => {
+            to_binary(&query::list_fee_exempt(deps, start_after, limit)?)
+        }
+        QueryMsg::GetBalance { account, denom } => {
+            to_binary(&query::balance(deps, account, denom)?)
+        }
+        QueryMsg::GetVestingSchedule { account, denom } => {
+            to_binary(&query::vesting_schedule(deps, env, account, denom)?)
+        }
+        QueryMsg::GetWithdrawable { account, denom } => {
+            to_binary(&query::withdrawable(deps, env, account, denom)?)
+        }
+        QueryMsg::GetAllBalances {
+            account,
+            start_after,
+            limit,
+        } => to_binary(&query::all_balances(deps, account, start_after, limit)?),
+        QueryMsg::GetSolvency { denom } => to_binary(&query::solvency(deps, env, denom)?),
+        QueryMsg::GetAllowance {
+            owner,
+            spender,
+            denom,
+        } => to_binary(&query::allowance(deps, owner, spender, denom)?),
+        QueryMsg::AllAllowances {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&query::all_allowances(deps, owner, start_after, limit)?),
+        QueryMsg::GetPauseStatus {} => to_binary(&query::pause_status(deps)?),
+        QueryMsg::GetPendingOwner {} => to_binary(&query::pending_owner(deps)?),
+        QueryMsg::GetPendingConfig {} => to_binary(&query::pending_config(deps, env)?),
+    }
+}
+
+pub mod query {
+    use cosmwasm_std::Empty;
+    use cw_storage_plus::{Bound, Map};
+
+    use crate::{
+        msg::{
+            AllAllowancesResponse, AllowanceInfo, ArbiterPaymentResponse, EscrowResponse,
+            GetAllBalancesResponse, GetAllowanceResponse, GetBalanceResponse, GetConfigResponse,
+            GetFeeForDenomResponse, GetFeeRecipientsResponse, GetFeesResponse, GetOwnerResponse,
+            GetPauseStatusResponse, GetPendingConfigResponse, GetPendingOwnerResponse,
+            GetReferralStatsResponse, GetSolvencyResponse, GetVestingScheduleResponse,
+            GetWithdrawableResponse, ListEscrowsResponse, ListFeeExemptResponse,
+            ListSupportedDenomsResponse, SupportedDenom,
+        },
+        state::{
+            Escrow, ALLOWANCES, ARBITER_PAYMENTS, ESCROWS, FEE_EXEMPT, PENDING_OWNER,
+            REFERRAL_EARNINGS, TOTAL_LIABILITIES,
+        },
+    };
+
+    use super::*;
+
+    pub fn owner(deps: Deps) -> StdResult<GetOwnerResponse> {
+        let state = STATE.load(deps.storage)?;
+        Ok(GetOwnerResponse { owner: state.owner })
+    }
+
+    pub fn fees(deps: Deps, env: Env) -> StdResult<GetFeesResponse> {
+        let state = STATE.load(deps.storage)?;
+
+        // A queued update that has reached its activation time is already in effect
+        let fees = match PENDING_CONFIG.may_load(deps.storage)? {
+            Some(pending) if pending.activates_at <= env.block.time => pending.fees,
+            _ => state.fees,
+        };
+
+        Ok(GetFeesResponse { fees })
+    }
+
+    pub fn config(deps: Deps) -> StdResult<GetConfigResponse> {
+        let state = STATE.load(deps.storage)?;
+        // The addresses that are actually paid, which is the fee recipient unless the fees are split
+        let fee_recipients = super::fee_recipients(&state)
+            .into_iter()
+            .map(|(address, weight)| FeeRecipient { address, weight })
+            .collect();
+        Ok(GetConfigResponse {
+            fee_recipients,
+            owner: state.owner,
+            max_recipients: state.max_recipients,
+            charge_transfer_fees: state.charge_transfer_fees,
+            referral_share: state.referral_share,
+            denom_allowlist: state.denom_allowlist,
+        })
+    }
+
+    pub fn list_supported_denoms(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<ListSupportedDenomsResponse> {
+        let state = STATE.load(deps.storage)?;
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+
+        let denoms = SUPPORTED_DENOMS
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                item.map(|(denom, info)| SupportedDenom {
+                    denom,
+                    display_name: info.display_name,
+                    decimals: info.decimals,
+                    min_send: info.min_send,
+                })
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(ListSupportedDenomsResponse {
+            denom_allowlist: state.denom_allowlist,
+            denoms,
+        })
+    }
+
+    pub fn arbiter_payment(deps: Deps, id: u64) -> StdResult<ArbiterPaymentResponse> {
+        let payment = ARBITER_PAYMENTS.load(deps.storage, id)?;
+        Ok(ArbiterPaymentResponse {
+            id,
+            sender: payment.sender,
+            recipient: payment.recipient,
+            arbiter: payment.arbiter,
+            funds: payment.funds,
+            expires: payment.expires,
+        })
+    }
+
+    pub fn escrow(deps: Deps, id: u64) -> StdResult<EscrowResponse> {
+        let escrow = ESCROWS.load(deps.storage, id)?;
+        Ok(escrow_response(id, escrow))
+    }
+
+    /// Lists the open escrows of an address from one of the escrow indexes, by sender or by recipient.
+    pub fn list_escrows(
+        deps: Deps,
+        index: Map<(&Addr, u64), Empty>,
+        address: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    ) -> StdResult<ListEscrowsResponse> {
+        let address: Addr = deps.api.addr_validate(&address)?;
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+
+        let escrows = index
+            .prefix(&address)
+            .keys(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|id| {
+                let id = id?;
+                Ok(escrow_response(id, ESCROWS.load(deps.storage, id)?))
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(ListEscrowsResponse { escrows })
+    }
+
+    fn escrow_response(id: u64, escrow: Escrow) -> EscrowResponse {
+        EscrowResponse {
+            id,
+            sender: escrow.sender,
+            shares: escrow.shares,
+            expires: escrow.expires,
+        }
+    }
+
+    pub fn referral_stats(deps: Deps, referrer: String) -> StdResult<GetReferralStatsResponse> {
+        let referrer: Addr = deps.api.addr_validate(&referrer)?;
+        let earnings = REFERRAL_EARNINGS
+            .prefix(&referrer)
+            .range(deps.storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(GetReferralStatsResponse { referrer, earnings })
+    }
+
+    pub fn fee_recipients(deps: Deps) -> StdResult<GetFeeRecipientsResponse> {
+        let state = STATE.load(deps.storage)?;
+        let recipients = super::fee_recipients(&state)
+            .into_iter()
+            .map(|(address, weight)| FeeRecipient { address, weight })
+            .collect();
+        Ok(GetFeeRecipientsResponse { recipients })
+    }
+
+    pub fn fee_for_denom(deps: Deps, env: Env, denom: String) -> StdResult<GetFeeForDenomResponse> {
+        // Queued changes that have reached their activation time are already in effect
+        let pending_bounds = PENDING_FEE_BOUNDS.may_load(deps.storage, denom.clone())?;
+        let bounds = match pending_bounds {
+            Some(ref pending) if pending.activates_at <= env.block.time => Some(pending.bounds.clone()),
+            _ => FEE_BOUNDS.may_load(deps.storage, denom.clone())?,
+        };
+
+        let pending = PENDING_DENOM_FEES.may_load(deps.storage, denom.clone())?;
+        let denom_fees = match pending {
+            Some(ref pending) if pending.activates_at <= env.block.time => pending.fees,
+            _ => DENOM_FEES.may_load(deps.storage, denom.clone())?,
+        };
+        let (fees, is_default) = match denom_fees {
+            Some(fees) => (fees, false),
+            None => (fees(deps, env.clone())?.fees, true),
+        };
+
+        Ok(GetFeeForDenomResponse {
+            denom,
+            fees,
+            is_default,
+            min_fee: bounds.as_ref().and_then(|bounds| bounds.min_fee),
+            max_fee: bounds.and_then(|bounds| bounds.max_fee),
+            pending_fees: pending.filter(|pending| pending.activates_at > env.block.time),
+            pending_bounds: pending_bounds.filter(|pending| pending.activates_at > env.block.time),
+        })
+    }
+
+    pub fn list_fee_exempt(
+        deps: Deps,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<ListFeeExemptResponse> {
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start_after: Option<Addr> = start_after
+            .map(|address| deps.api.addr_validate(&address))
+            .transpose()?;
+        let start = start_after.as_ref().map(Bound::exclusive);
+
+        let addresses = FEE_EXEMPT
+            .keys(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(ListFeeExemptResponse { addresses })
+    }
+
+    pub fn balance(deps: Deps, account: String, denom: String) -> StdResult<GetBalanceResponse> {
+        let address: Addr = deps.api.addr_validate(&account)?;
+
+        let balance = BALANCES
+            .may_load(deps.storage, (&address, denom))?
+            .unwrap_or_default();
+
+        Ok(GetBalanceResponse { balance })
+    }
+
+    pub fn vesting_schedule(
+        deps: Deps,
+        env: Env,
+        account: String,
+        denom: String,
+    ) -> StdResult<GetVestingScheduleResponse> {
+        let address: Addr = deps.api.addr_validate(&account)?;
+        let schedules: Vec<VestingSchedule> = vesting_schedules(deps.storage, &address, &denom)?
+            .into_iter()
+            .map(|(_, schedule)| schedule)
+            .collect();
+
+        let vested: Uint128 = schedules
+            .iter()
+            .map(|schedule| schedule.vested(env.block.time))
+            .sum();
+        let total: Uint128 = schedules.iter().map(|schedule| schedule.total).sum();
+
+        Ok(GetVestingScheduleResponse {
+            schedules,
+            vested,
+            locked: total - vested,
+        })
+    }
+
+    pub fn withdrawable(
+        deps: Deps,
+        env: Env,
+        account: String,
+        denom: String,
+    ) -> StdResult<GetWithdrawableResponse> {
+        let address: Addr = deps.api.addr_validate(&account)?;
+        let balance = BALANCES
+            .may_load(deps.storage, (&address, denom.clone()))?
+            .unwrap_or_default();
+        let locked = locked_amount(deps.storage, &address, &denom, env.block.time)?;
+
+        Ok(GetWithdrawableResponse {
+            balance,
+            locked,
+            withdrawable: balance.saturating_sub(locked),
+        })
+    }
+
+    pub fn all_balances(
+        deps: Deps,
+        account: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<GetAllBalancesResponse> {
+        let address: Addr = deps.api.addr_validate(&account)?;
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start = start_after.map(Bound::exclusive);
+
+        // Zero balances are skipped so that every page only contains coins the account is owed
+        let balances = BALANCES
+            .prefix(&address)
+            .range(deps.storage, start, None, Order::Ascending)
+            .filter(|item| !matches!(item, Ok((_, amount)) if amount.is_zero()))
+            .take(limit)
+            .map(|item| item.map(|(denom, amount)| Coin { denom, amount }))
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(GetAllBalancesResponse { balances })
+    }
+
+    pub fn solvency(deps: Deps, env: Env, denom: String) -> StdResult<GetSolvencyResponse> {
+        let liabilities = TOTAL_LIABILITIES
+            .may_load(deps.storage, denom.clone())?
+            .unwrap_or_default();
+        let balance = query_asset_balance(&deps.querier, &env.contract.address, &denom)?;
+
+        Ok(GetSolvencyResponse {
+            denom,
+            liabilities,
+            balance,
+            solvent: balance >= liabilities,
+        })
+    }
+
+    pub fn allowance(
+        deps: Deps,
+        owner: String,
+        spender: String,
+        denom: String,
+    ) -> StdResult<GetAllowanceResponse> {
+        let owner: Addr = deps.api.addr_validate(&owner)?;
+        let spender: Addr = deps.api.addr_validate(&spender)?;
+
+        let allowance = ALLOWANCES
+            .may_load(deps.storage, (&owner, &spender, denom))?
+            .unwrap_or_default();
+
+        Ok(GetAllowanceResponse {
+            amount: allowance.amount,
+            expires: allowance.expires,
+        })
+    }
+
+    pub fn all_allowances(
+        deps: Deps,
+        owner: String,
+        start_after: Option<(String, String)>,
+        limit: Option<u32>,
+    ) -> StdResult<AllAllowancesResponse> {
+        let owner: Addr = deps.api.addr_validate(&owner)?;
+        let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+        let start_after = start_after
+            .map(|(spender, denom)| deps.api.addr_validate(&spender).map(|spender| (spender, denom)))
+            .transpose()?;
+        let start = start_after
+            .as_ref()
+            .map(|(spender, denom)| Bound::exclusive((spender, denom.clone())));
+
+        let allowances = ALLOWANCES
+            .sub_prefix(&owner)
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit)
+            .map(|item| {
+                item.map(|((spender, denom), allowance)| AllowanceInfo {
+                    spender,
+                    denom,
+                    amount: allowance.amount,
+                    expires: allowance.expires,
+                })
+            })
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(AllAllowancesResponse { allowances })
+    }
+
+    pub fn pause_status(deps: Deps) -> StdResult<GetPauseStatusResponse> {
+        let state = STATE.load(deps.storage)?;
+        Ok(GetPauseStatusResponse {
+            deposits_paused: state.deposits_paused,
+            withdrawals_paused: state.withdrawals_paused,
+            pauser: state.pauser,
+        })
+    }
+
+    pub fn pending_owner(deps: Deps) -> StdResult<GetPendingOwnerResponse> {
+        let pending = PENDING_OWNER.may_load(deps.storage)?;
+        Ok(GetPendingOwnerResponse {
+            pending_owner: pending.as_ref().map(|p| p.new_owner.clone()),
+            expires: pending.and_then(|p| p.expires),
+        })
+    }
+
+    pub fn pending_config(deps: Deps, env: Env) -> StdResult<GetPendingConfigResponse> {
+        let state = STATE.load(deps.storage)?;
+
+        // Only updates that have not activated yet are still pending
+        let pending = PENDING_CONFIG
+            .may_load(deps.storage)?
+            .filter(|p| p.activates_at > env.block.time);
+
+        Ok(GetPendingConfigResponse {
+            fees: pending.as_ref().map(|p| p.fees),
+            activates_at: pending.map(|p| p.activates_at),
+            fee_update_delay: state.fee_update_delay,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::msg::{
+        AllAllowancesResponse, ArbiterPaymentResponse, EscrowResponse, GetAllBalancesResponse,
+        GetAllowanceResponse, GetBalanceResponse, GetConfigResponse, GetFeeForDenomResponse,
+        GetFeeRecipientsResponse, GetFeesResponse, GetOwnerResponse, GetPauseStatusResponse,
+        GetPendingConfigResponse, GetPendingOwnerResponse, GetReferralStatsResponse,
+        GetSolvencyResponse, GetVestingScheduleResponse, GetWithdrawableResponse, ListEscrowsResponse,
+        ListFeeExemptResponse, ListSupportedDenomsResponse, ReceiveMsg, SupportedDenom,
+    };
+
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{coin, coins, from_binary, BankMsg, CosmosMsg, Uint128, Addr};
+    use cw2::get_contract_version;
+    use cw_utils::Expiration;
+
+    use crate::state::{PendingDenomFee, StateV0_1_0, BALANCES, STATE_V0_1_0};
+
+    #[test]
+    fn initialization_basic() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+        let info = mock_info("creator", &coins(0, "usei"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
+
+        // it worked, let's query the state
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwner {}).unwrap();
+        let value: GetOwnerResponse = from_binary(&res).unwrap();
+        assert_eq!("creator", value.owner);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFees {}).unwrap();
+        let value: GetFeesResponse = from_binary(&res).unwrap();
+        assert_eq!(1000, value.fees);
+    }
+
+    #[test]
+    fn initialization_fail() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 10001,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+        let info = mock_info("creator", &coins(0, "usei"));
+
+        // we can just call .unwrap() to assert this was a success
+        let res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::InvalidFeePercentageError { fees: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn send_basic() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg);
 
         // ensure initial balance of account1 is 0 before a sent
         let msg = QueryMsg::GetBalance {
-            account: "account1".to_owned(),
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.balance);
+
+        // disburse an initial send to two accounts (both accounts should have 4 after fees in their allowances i.e. balances)
+        let info = mock_info("sender", &coins(10, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        // query to check updated balance of account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(4), value.balance);
+
+        // query to check updated balance of account 2
+        let msg = QueryMsg::GetBalance {
+            account: "account2".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(5), value.balance);
+
+        // retrieve the owner to check if fees were collected
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwner {}).unwrap();
+        let value: GetOwnerResponse = from_binary(&res).unwrap();  
+        let owner: Addr = value.owner;
+
+        // retrieve the balance of the owner to see if fees were collected
+        let msg = QueryMsg::GetBalance {
+            account: owner.to_string(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1), value.balance);
+
+    }
+
+    #[test]
+    fn send_no_funds_fail() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // a send without funds is rejected rather than succeeding as a no-op
+        let info = mock_info("sender", &[]);
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::NoFundsError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the same applies to split sends
+        let info = mock_info("sender", &[]);
+        let msg: ExecuteMsg = ExecuteMsg::SendSplit {
+            recipients: vec![Recipient { address: "account1".to_owned(), weight: 1 }],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::NoFundsError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn send_zero_amount_fail() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // every coin sent must have a non-zero amount
+        let info = mock_info("sender", &[coin(10, "usei"), coin(0, "uatom")]);
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::ZeroAmountError { denom } => assert_eq!("uatom", denom),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // nothing was credited by the rejected send
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.balance);
+    }
+
+    #[test]
+    fn send_duplicate_recipient_fail() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // sending to the same account twice is rejected
+        let info = mock_info("sender", &coins(10, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account1".to_owned(),
+            referrer: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::DuplicateRecipientError { address } => assert_eq!("account1", address),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn withdraw_zero_amount_fail() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("sender", &coins(10, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // withdrawing nothing is rejected even with a balance
+        let info = mock_info("account1", &[]);
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::zero(),
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::ZeroAmountError { denom: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // transferring a zero amount is rejected as well
+        let info = mock_info("account1", &[]);
+        let msg = ExecuteMsg::TransferBalance {
+            to: "account2".to_owned(),
+            amount: Uint128::zero(),
+            denom: "usei".to_owned(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::ZeroAmountError { denom: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn send_multiple() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg);
+
+        // disburse an initial send to two accounts
+        let info = mock_info("sender", &coins(51, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        // query to check updated balance of account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(23), value.balance);
+
+        // query to check updated balance of account 2
+        let msg = QueryMsg::GetBalance {
+            account: "account2".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(23), value.balance);
+
+        // disburse an another send to two accounts (both accounts should have 5 in their allowances i.e. balances)
+        let info = mock_info("sender", &coins(65, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account3".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        // query to check updated balance of account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(52), value.balance);
+
+        // query to check updated balance of account 3
+        let msg = QueryMsg::GetBalance {
+            account: "account3".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(30), value.balance);
+    }
+
+    #[test]
+    fn send_multiple_currencies() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg);
+
+        // disburse an initial send to two accounts
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        // query to check updated balance of account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+
+        // query to check updated balance of account 2
+        let msg = QueryMsg::GetBalance {
+            account: "account2".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+
+        // disburse an another send to two accounts but with a different currency this time
+        let info = mock_info("sender", &coins(50, "wei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        // query to check updated balance of account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "wei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(22), value.balance);
+
+        // query to check updated balance of account 3
+        let msg = QueryMsg::GetBalance {
+            account: "account2".to_owned(),
+            denom: "wei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(23), value.balance);
+    }
+
+    #[test]
+    fn withdraw_basic() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // disburse an initial send to two accounts
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        // query to check updated balance of account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+
+        // account 1 withdraws money from the contract
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::new(25),
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let info = mock_info("account1", &coins(0, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let msg = res.messages.first().expect("no message");
+        assert_eq!(
+            msg.msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "account1".to_owned(),
+                amount: coins(25, "usei")
+            })
+        );
+
+        // query to check updated balance for account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(20), value.balance);
+
+        // query to check updated balance for account 2
+        let msg = QueryMsg::GetBalance {
+            account: "account2".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+    }
+
+    #[test]
+    fn withdraw_all() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // disburse an initial send to two accounts
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        // query to check updated balance of account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+
+        // account 1 withdraws money from the contract
+        let msg = ExecuteMsg::WithdrawAll {
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let info = mock_info("account1", &coins(0, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let msg = res.messages.first().expect("no message");
+        assert_eq!(
+            msg.msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "account1".to_owned(),
+                amount: coins(45, "usei")
+            })
+        );
+
+        // query to check updated balance for account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.balance);
+
+        // query to check updated balance for account 2
+        let msg = QueryMsg::GetBalance {
+            account: "account2".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+    }
+
+    #[test]
+    fn withdraw_fail() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // disburse an initial send to two accounts
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        // query to check updated balance of account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+
+        // account 1 over-withdraws money from the contract
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::new(46),
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let info = mock_info("account1", &coins(0, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::InsufficientBalanceError {
+                denom: _,
+                balance: _,
+                requested: _,
+            } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // query to check updated balance for account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+
+        // query to check updated balance for account 2
+        let msg = QueryMsg::GetBalance {
+            account: "account2".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+    }
+
+    #[test]
+    fn withdraw_multiple() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // disburse an initial send to two accounts
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+
+        // account 1 withdraws money from the contract
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::new(25),
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let info = mock_info("account1", &coins(0, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let msg = res.messages.first().expect("no message");
+        assert_eq!(
+            msg.msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "account1".to_owned(),
+                amount: coins(25, "usei")
+            })
+        );
+
+        // query to check updated balance for account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(20), value.balance);
+
+        // account 1 withdraws money from the contract a second time
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::new(19),
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let info = mock_info("account1", &coins(0, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(1, res.messages.len());
+        let msg = res.messages.first().expect("no message");
+        assert_eq!(
+            msg.msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "account1".to_owned(),
+                amount: coins(19, "usei")
+            })
+        );
+
+        // query to check updated balance for account 1
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1), value.balance);
+    }
+
+    #[test]
+    fn ownership_transfer() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // credit the original owner with fees (10 usei)
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner can propose a new owner
+        let msg = ExecuteMsg::ProposeNewOwner {
+            new_owner: "new_owner".to_owned(),
+            expires: None,
+        };
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPendingOwner {}).unwrap();
+        let value: GetPendingOwnerResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(Addr::unchecked("new_owner")), value.pending_owner);
+
+        // only the proposed owner can accept ownership
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AcceptOwnership {}).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("new_owner", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AcceptOwnership {}).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwner {}).unwrap();
+        let value: GetOwnerResponse = from_binary(&res).unwrap();
+        assert_eq!("new_owner", value.owner);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPendingOwner {}).unwrap();
+        let value: GetPendingOwnerResponse = from_binary(&res).unwrap();
+        assert_eq!(None, value.pending_owner);
+
+        // future fees are credited to the new owner
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "new_owner".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(10), value.balance);
+
+        // the previous owner can still withdraw the fees they collected
+        let msg = ExecuteMsg::WithdrawAll {
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = res.messages.first().expect("no message");
+        assert_eq!(
+            msg.msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "creator".to_owned(),
+                amount: coins(10, "usei")
+            })
+        );
+    }
+
+    #[test]
+    fn ownership_transfer_expired() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // propose a new owner that must accept within 10 blocks
+        let env = mock_env();
+        let msg = ExecuteMsg::ProposeNewOwner {
+            new_owner: "new_owner".to_owned(),
+            expires: Some(Expiration::AtHeight(env.block.height + 10)),
+        };
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        // accepting after the expiry fails
+        let mut env = mock_env();
+        env.block.height += 10;
+        let info = mock_info("new_owner", &[]);
+        let res = execute(deps.as_mut(), env, info, ExecuteMsg::AcceptOwnership {}).unwrap_err();
+        match res {
+            ContractError::OwnershipProposalExpiredError { expires: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the owner cancels the proposal and nothing can be accepted afterwards
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::CancelOwnershipProposal {}).unwrap();
+
+        let info = mock_info("new_owner", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::AcceptOwnership {}).unwrap_err();
+        match res {
+            ContractError::NoPendingOwnerError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwner {}).unwrap();
+        let value: GetOwnerResponse = from_binary(&res).unwrap();
+        assert_eq!("creator", value.owner);
+    }
+
+    #[test]
+    fn update_fees() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: Some(100),
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner can update the fees
+        let info = mock_info("sender", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::UpdateFees { fees: 2000 }).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // updated fees are validated the same way as on instantiation
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::UpdateFees { fees: 10001 }).unwrap_err();
+        match res {
+            ContractError::InvalidFeePercentageError { fees: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // queue the fee update
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::UpdateFees { fees: 2000 }).unwrap();
+        assert_eq!("fee_update_scheduled", res.events[0].ty);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPendingConfig {}).unwrap();
+        let value: GetPendingConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(2000), value.fees);
+        assert_eq!(Some(mock_env().block.time.plus_seconds(100)), value.activates_at);
+        assert_eq!(100, value.fee_update_delay);
+
+        // sends before the activation time still use the current fees
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.events.len());
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFees {}).unwrap();
+        let value: GetFeesResponse = from_binary(&res).unwrap();
+        assert_eq!(1000, value.fees);
+
+        // once the delay has passed the new fees are applied on the next send
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetFees {}).unwrap();
+        let value: GetFeesResponse = from_binary(&res).unwrap();
+        assert_eq!(2000, value.fees);
+
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert_eq!(1, res.events.len());
+        assert_eq!("fee_update_activated", res.events[0].ty);
+
+        let res = query(deps.as_ref(), env, QueryMsg::GetPendingConfig {}).unwrap();
+        let value: GetPendingConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(None, value.fees);
+
+        // the owner collected 10 usei from the first send and 20 usei from the second
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(30), value.balance);
+    }
+
+    #[test]
+    fn send_fractional_fees() {
+        let mut deps = mock_dependencies();
+
+        // 25 basis points i.e. 0.25%
+        let msg = InstantiateMsg {
+            fees: 25,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 0.25% of 399 usei rounds down to no fees at all, everything is split between the accounts
+        let info = mock_info("sender", &coins(399, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.balance);
+
+        let msg = QueryMsg::GetBalance {
+            account: "account2".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(200), value.balance);
+
+        // 0.25% of 10_000 usei is exactly 25 usei
+        let info = mock_info("sender", &coins(10_000, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(25), value.balance);
+    }
+
+    #[test]
+    fn migrate_percentage_fees() {
+        let mut deps = mock_dependencies();
+
+        // store the contract as it was laid out by version 0.1.0
+        let legacy = StateV0_1_0 {
+            owner: Addr::unchecked("creator"),
+            fees: 10,
+        };
+        STATE_V0_1_0.save(deps.as_mut().storage, &legacy).unwrap();
+        BALANCES
+            .save(deps.as_mut().storage, (&Addr::unchecked("account1"), "usei".to_owned()), &Uint128::new(45))
+            .unwrap();
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+
+        let _res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        // 10% is converted to 1000 basis points
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFees {}).unwrap();
+        let value: GetFeesResponse = from_binary(&res).unwrap();
+        assert_eq!(1000, value.fees);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwner {}).unwrap();
+        let value: GetOwnerResponse = from_binary(&res).unwrap();
+        assert_eq!("creator", value.owner);
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPendingConfig {}).unwrap();
+        let value: GetPendingConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(DEFAULT_FEE_UPDATE_DELAY, value.fee_update_delay);
+
+        // balances credited before the migration are untouched
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.balance);
+
+        // the total liabilities are computed from the existing balances
+        let msg = QueryMsg::GetSolvency {
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetSolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(45), value.liabilities);
+
+        let version = get_contract_version(deps.as_ref().storage).unwrap();
+        assert_eq!(CONTRACT_VERSION, version.version);
+
+        // migrating again at the current version leaves the state as is
+        let _res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFees {}).unwrap();
+        let value: GetFeesResponse = from_binary(&res).unwrap();
+        assert_eq!(1000, value.fees);
+    }
+
+    #[test]
+    fn migrate_fail() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // a newer version cannot be downgraded
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        match res {
+            ContractError::MigrationDowngradeError { stored: _, current: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // a different contract cannot be migrated to this one
+        set_contract_version(deps.as_mut().storage, "crates.io:cw20-base", "0.1.0").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        match res {
+            ContractError::InvalidContractNameError { expected: _, actual: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the stored version must be a semantic version
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "v1").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+        match res {
+            ContractError::InvalidVersionError { version: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn send_split() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // 900 usei are left after fees and split 7:2:1
+        let info = mock_info("sender", &coins(1000, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::SendSplit {
+            recipients: vec![
+                Recipient { address: "account1".to_owned(), weight: 7 },
+                Recipient { address: "account2".to_owned(), weight: 2 },
+                Recipient { address: "account3".to_owned(), weight: 1 },
+            ],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        for (account, expected) in [("account1", 630), ("account2", 180), ("account3", 90), ("creator", 100)] {
+            let msg = QueryMsg::GetBalance {
+                account: account.to_owned(),
+                denom: "usei".to_owned(),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: GetBalanceResponse = from_binary(&res).unwrap();
+            assert_eq!(Uint128::new(expected), value.balance);
+        }
+
+        // 90 wei are left after fees, each of the four accounts is owed 22.5 so the 2 wei of dust go to
+        // the last two accounts
+        let info = mock_info("sender", &coins(100, "wei"));
+        let msg: ExecuteMsg = ExecuteMsg::SendSplit {
+            recipients: vec![
+                Recipient { address: "account1".to_owned(), weight: 1 },
+                Recipient { address: "account2".to_owned(), weight: 1 },
+                Recipient { address: "account3".to_owned(), weight: 1 },
+                Recipient { address: "account4".to_owned(), weight: 1 },
+            ],
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        for (account, expected) in [("account1", 22), ("account2", 22), ("account3", 23), ("account4", 23)] {
+            let msg = QueryMsg::GetBalance {
+                account: account.to_owned(),
+                denom: "wei".to_owned(),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: GetBalanceResponse = from_binary(&res).unwrap();
+            assert_eq!(Uint128::new(expected), value.balance);
+        }
+    }
+
+    #[test]
+    fn send_split_fail() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: Some(2),
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // no recipients
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg = ExecuteMsg::SendSplit { recipients: vec![] };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        match res {
+            ContractError::NoRecipientsError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // more recipients than the configured maximum
+        let msg = ExecuteMsg::SendSplit {
+            recipients: vec![
+                Recipient { address: "account1".to_owned(), weight: 1 },
+                Recipient { address: "account2".to_owned(), weight: 1 },
+                Recipient { address: "account3".to_owned(), weight: 1 },
+            ],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        match res {
+            ContractError::TooManyRecipientsError { count: 3, max: 2 } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // zero weight
+        let msg = ExecuteMsg::SendSplit {
+            recipients: vec![
+                Recipient { address: "account1".to_owned(), weight: 1 },
+                Recipient { address: "account2".to_owned(), weight: 0 },
+            ],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), msg).unwrap_err();
+        match res {
+            ContractError::ZeroWeightError { address: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // duplicate recipient
+        let msg = ExecuteMsg::SendSplit {
+            recipients: vec![
+                Recipient { address: "account1".to_owned(), weight: 1 },
+                Recipient { address: "account1".to_owned(), weight: 2 },
+            ],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::DuplicateRecipientError { address: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // nothing was credited
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.balance);
+    }
+
+    #[test]
+    fn query_all_balances() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // disburse a send in three currencies to two accounts
+        let info = mock_info("sender", &[coin(100, "uatom"), coin(100, "usei"), coin(50, "wei")]);
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // account 1 withdraws all of its usei, which should no longer be listed
+        let msg = ExecuteMsg::WithdrawAll {
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let info = mock_info("account1", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::GetAllBalances {
+            account: "account1".to_owned(),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetAllBalancesResponse = from_binary(&res).unwrap();
+        assert_eq!(vec![coin(45, "uatom"), coin(22, "wei")], value.balances);
+
+        // paginate through the balances of account 2 one coin at a time
+        let msg = QueryMsg::GetAllBalances {
+            account: "account2".to_owned(),
+            start_after: None,
+            limit: Some(1),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetAllBalancesResponse = from_binary(&res).unwrap();
+        assert_eq!(vec![coin(45, "uatom")], value.balances);
+
+        let msg = QueryMsg::GetAllBalances {
+            account: "account2".to_owned(),
+            start_after: Some("uatom".to_owned()),
+            limit: Some(1),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetAllBalancesResponse = from_binary(&res).unwrap();
+        assert_eq!(vec![coin(45, "usei")], value.balances);
+
+        let msg = QueryMsg::GetAllBalances {
+            account: "account2".to_owned(),
+            start_after: Some("wei".to_owned()),
+            limit: Some(1),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetAllBalancesResponse = from_binary(&res).unwrap();
+        assert!(value.balances.is_empty());
+    }
+
+    #[test]
+    fn query_solvency() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // disburse two sends, the contract now owes 150 usei in total
+        for amount in [100, 50] {
+            let info = mock_info("sender", &coins(amount, "usei"));
+            let msg: ExecuteMsg = ExecuteMsg::Send {
+                account1: "account1".to_owned(),
+                account2: "account2".to_owned(),
+                referrer: None,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(150, "usei"));
+
+        let msg = QueryMsg::GetSolvency {
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: GetSolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(150), value.liabilities);
+        assert_eq!(Uint128::new(150), value.balance);
+        assert!(value.solvent);
+
+        // account 1 withdraws part of its balance, which lowers the liabilities
+        let msg_withdraw = ExecuteMsg::Withdraw {
+            amount: Uint128::new(30),
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let info = mock_info("account1", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg_withdraw).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(120, "usei"));
+
+        let res = query(deps.as_ref(), mock_env(), msg.clone()).unwrap();
+        let value: GetSolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(120), value.liabilities);
+        assert!(value.solvent);
+
+        // the ledger drifting from the bank balance is reported
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(119, "usei"));
+
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetSolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(119), value.balance);
+        assert!(!value.solvent);
+    }
+
+    #[test]
+    fn sweep_surplus() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the contract owes 100 usei but holds an extra 30 usei sent without going through Send
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(130, "usei"));
+
+        // only the owner can sweep
+        let msg = ExecuteMsg::SweepSurplus {
+            denom: "usei".to_owned(),
+            recipient: "treasury".to_owned(),
+        };
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // only the surplus is paid out
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        assert_eq!(1, res.messages.len());
+        let sent = res.messages.first().expect("no message");
+        assert_eq!(
+            sent.msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "treasury".to_owned(),
+                amount: coins(30, "usei")
+            })
+        );
+
+        // once swept there is no surplus left
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, coins(100, "usei"));
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::NoSurplusError { denom: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn denom_fees() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner can set denom fees
+        let msg = ExecuteMsg::SetDenomFee {
+            denom: "uatom".to_owned(),
+            fees: 200,
+        };
+        let info = mock_info("sender", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // denom fees are validated the same way as the global fees
+        let info = mock_info("creator", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::SetDenomFee {
+                denom: "uatom".to_owned(),
+                fees: 10001,
+            },
+        )
+        .unwrap_err();
+        match res {
+            ContractError::InvalidFeePercentageError { fees: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!("fee_update_scheduled", res.events[0].ty);
+
+        // denom fees are queued behind the fee update delay like the global fees
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFeeForDenom { denom: "uatom".to_owned() }).unwrap();
+        let value: GetFeeForDenomResponse = from_binary(&res).unwrap();
+        assert_eq!(1000, value.fees);
+        assert!(value.is_default);
+        let activates_at = mock_env().block.time.plus_seconds(DEFAULT_FEE_UPDATE_DELAY);
+        assert_eq!(Some(PendingDenomFee { fees: Some(200), activates_at }), value.pending_fees);
+
+        let info = mock_info("sender", &coins(1000, "uatom"));
+        let send_msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "uatom".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(100), value.balance);
+
+        let mut env = mock_env();
+        env.block.time = activates_at;
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetFeeForDenom { denom: "uatom".to_owned() }).unwrap();
+        let value: GetFeeForDenomResponse = from_binary(&res).unwrap();
+        assert_eq!(200, value.fees);
+        assert!(!value.is_default);
+        assert_eq!(None, value.pending_fees);
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetFeeForDenom { denom: "usei".to_owned() }).unwrap();
+        let value: GetFeeForDenomResponse = from_binary(&res).unwrap();
+        assert_eq!(1000, value.fees);
+        assert!(value.is_default);
+
+        // each denom in a send is charged its own fees once the change has activated
+        let info = mock_info("sender", &[coin(1000, "uatom"), coin(1000, "usei")]);
+        let res = execute(deps.as_mut(), env.clone(), info, send_msg.clone()).unwrap();
+        assert_eq!("fee_update_activated", res.events[0].ty);
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "uatom".to_owned(),
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(120), value.balance);
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "uatom".to_owned(),
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(940), value.balance);
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(100), value.balance);
+
+        // clearing the denom fees is queued as well, after which the denom falls back to the global fees
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::ClearDenomFee { denom: "uatom".to_owned() }).unwrap();
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetFeeForDenom { denom: "uatom".to_owned() }).unwrap();
+        let value: GetFeeForDenomResponse = from_binary(&res).unwrap();
+        assert_eq!(200, value.fees);
+
+        env.block.time = env.block.time.plus_seconds(DEFAULT_FEE_UPDATE_DELAY);
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetFeeForDenom { denom: "uatom".to_owned() }).unwrap();
+        let value: GetFeeForDenomResponse = from_binary(&res).unwrap();
+        assert_eq!(1000, value.fees);
+        assert!(value.is_default);
+
+        let info = mock_info("sender", &coins(1000, "uatom"));
+        let _res = execute(deps.as_mut(), env.clone(), info, send_msg).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "uatom".to_owned(),
+        };
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(220), value.balance);
+    }
+
+    #[test]
+    fn fee_exempt() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner can manage fee exemptions
+        let msg = ExecuteMsg::AddFeeExempt {
+            address: "treasury".to_owned(),
+        };
+        let info = mock_info("sender", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // exempt senders are not charged fees and the send is marked with an event
+        let send_msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let info = mock_info("treasury", &coins(100, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+        assert_eq!(1, res.events.len());
+        assert_eq!("fee_exempt_send", res.events[0].ty);
+
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(50), value.balance);
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.balance);
+
+        // other senders are still charged fees
+        let info = mock_info("sender", &coins(100, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+        assert_eq!(0, res.events.len());
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(10), value.balance);
+
+        // exempt senders are listed in address order
+        for address in ["partner2", "partner1"] {
+            let info = mock_info("creator", &[]);
+            let msg = ExecuteMsg::AddFeeExempt {
+                address: address.to_owned(),
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        let msg = QueryMsg::ListFeeExempt {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ListFeeExemptResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![Addr::unchecked("partner1"), Addr::unchecked("partner2")],
+            value.addresses
+        );
+
+        let msg = QueryMsg::ListFeeExempt {
+            start_after: Some("partner2".to_owned()),
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ListFeeExemptResponse = from_binary(&res).unwrap();
+        assert_eq!(vec![Addr::unchecked("treasury")], value.addresses);
+
+        // once removed the sender is charged fees again
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::RemoveFeeExempt {
+            address: "treasury".to_owned(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("treasury", &coins(100, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, send_msg).unwrap();
+        assert_eq!(0, res.events.len());
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(20), value.balance);
+    }
+
+    #[test]
+    fn fee_recipients() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: Some(vec![
+                Recipient { address: "creator".to_owned(), weight: 70 },
+                Recipient { address: "treasury".to_owned(), weight: 20 },
+                Recipient { address: "referrals".to_owned(), weight: 10 },
+            ]),
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the 100 usei of fees are split 70:20:10
+        let send_msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let info = mock_info("sender", &coins(1000, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+
+        for (account, expected) in [("creator", 70), ("treasury", 20), ("referrals", 10)] {
+            let msg = QueryMsg::GetBalance {
+                account: account.to_owned(),
+                denom: "usei".to_owned(),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: GetBalanceResponse = from_binary(&res).unwrap();
+            assert_eq!(Uint128::new(expected), value.balance);
+        }
+
+        // the config reports the addresses that are actually paid
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(3, value.fee_recipients.len());
+
+        // a single fee recipient cannot be set while the fees are split
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateFeeRecipient {
+            fee_recipient: Some("treasury".to_owned()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::ConflictingFeeRecipientsError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // a single unit of fees is dust and goes to the last fee recipient
+        let info = mock_info("sender", &coins(10, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "referrals".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(11), value.balance);
+
+        // only the owner can update the fee recipients
+        let msg = ExecuteMsg::UpdateFeeRecipients { recipients: vec![] };
+        let info = mock_info("treasury", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // fee recipients are validated the same way as split send recipients
+        let info = mock_info("creator", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::UpdateFeeRecipients {
+                recipients: vec![Recipient { address: "treasury".to_owned(), weight: 0 }],
+            },
+        )
+        .unwrap_err();
+        match res {
+            ContractError::ZeroWeightError { address: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // an empty list sends all fees to the owner
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFeeRecipients {}).unwrap();
+        let value: GetFeeRecipientsResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![FeeRecipient { address: Addr::unchecked("creator"), weight: 1 }],
+            value.recipients
+        );
+
+        let info = mock_info("sender", &coins(1000, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(170), value.balance);
+    }
+
+    #[test]
+    fn fee_recipient() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: Some("treasury".to_owned()),
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(Addr::unchecked("creator"), value.owner);
+        assert_eq!(
+            vec![FeeRecipient { address: Addr::unchecked("treasury"), weight: 1 }],
+            value.fee_recipients
+        );
+
+        // the fees cannot also be split between weighted fee recipients while a fee recipient is set
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateFeeRecipients {
+            recipients: vec![Recipient { address: "pool".to_owned(), weight: 1 }],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::ConflictingFeeRecipientsError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the fees are collected by the fee recipient rather than the owner
+        let send_msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let info = mock_info("sender", &coins(100, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "treasury".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(10), value.balance);
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.balance);
+
+        // only the owner can change the fee recipient
+        let msg = ExecuteMsg::UpdateFeeRecipient { fee_recipient: None };
+        let info = mock_info("treasury", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // without a fee recipient the owner collects the fees again
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(
+            vec![FeeRecipient { address: Addr::unchecked("creator"), weight: 1 }],
+            value.fee_recipients
+        );
+
+        let info = mock_info("sender", &coins(100, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(10), value.balance);
+    }
+
+    #[test]
+    fn fee_bounds() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner can bound the fees
+        let msg = ExecuteMsg::SetFeeBounds {
+            denom: "usei".to_owned(),
+            min_fee: Some(Uint128::new(5)),
+            max_fee: Some(Uint128::new(50)),
+        };
+        let info = mock_info("sender", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the minimum fee cannot be more than the maximum fee
+        let info = mock_info("creator", &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info,
+            ExecuteMsg::SetFeeBounds {
+                denom: "usei".to_owned(),
+                min_fee: Some(Uint128::new(50)),
+                max_fee: Some(Uint128::new(5)),
+            },
+        )
+        .unwrap_err();
+        match res {
+            ContractError::InvalidFeeBoundsError { min_fee: _, max_fee: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("creator", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!("fee_bounds_update_scheduled", res.events[0].ty);
+
+        // the bounds are queued behind the fee update delay
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetFeeForDenom { denom: "usei".to_owned() }).unwrap();
+        let value: GetFeeForDenomResponse = from_binary(&res).unwrap();
+        assert_eq!(None, value.min_fee);
+        assert_eq!(None, value.max_fee);
+        let activates_at = mock_env().block.time.plus_seconds(DEFAULT_FEE_UPDATE_DELAY);
+        assert_eq!(activates_at, value.pending_bounds.unwrap().activates_at);
+
+        let send_msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+
+        // until then a fee of 2 usei is charged as is
+        let info = mock_info("sender", &coins(20, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(2), value.balance);
+
+        let mut env = mock_env();
+        env.block.time = activates_at;
+
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetFeeForDenom { denom: "usei".to_owned() }).unwrap();
+        let value: GetFeeForDenomResponse = from_binary(&res).unwrap();
+        assert_eq!(Some(Uint128::new(5)), value.min_fee);
+        assert_eq!(Some(Uint128::new(50)), value.max_fee);
+        assert_eq!(None, value.pending_bounds);
+
+        // once active a fee of 2 usei is raised to the minimum fee
+        let info = mock_info("sender", &coins(20, "usei"));
+        let res = execute(deps.as_mut(), env.clone(), info, send_msg.clone()).unwrap();
+        assert_eq!("fee_bounds_update_activated", res.events[0].ty);
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(7), value.balance);
+
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(16), value.balance);
+
+        // a fee of 100 usei is lowered to the maximum fee
+        let info = mock_info("sender", &coins(1000, "usei"));
+        let _res = execute(deps.as_mut(), env.clone(), info, send_msg.clone()).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(57), value.balance);
+
+        // sends that cannot cover the minimum fee are rejected
+        let info = mock_info("sender", &coins(3, "usei"));
+        let res = execute(deps.as_mut(), env.clone(), info, send_msg.clone()).unwrap_err();
+        match res {
+            ContractError::BelowMinimumFeeError { denom: _, amount: _, min_fee: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // clearing both bounds charges the plain percentage again once the delay has passed
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::SetFeeBounds {
+            denom: "usei".to_owned(),
+            min_fee: None,
+            max_fee: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        env.block.time = env.block.time.plus_seconds(DEFAULT_FEE_UPDATE_DELAY);
+
+        let info = mock_info("sender", &coins(20, "usei"));
+        let _res = execute(deps.as_mut(), env.clone(), info, send_msg).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "creator".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(59), value.balance);
+    }
+
+    #[test]
+    fn referrals() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: Some(2000),
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // senders cannot refer themselves
+        let info = mock_info("sender", &coins(1000, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: Some("sender".to_owned()),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::SelfReferralError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // 20% of the 100 usei of fees go to the referrer
+        let send_msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: Some("integrator".to_owned()),
+        };
+        let info = mock_info("sender", &[coin(1000, "usei"), coin(100, "uatom")]);
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+
+        for (account, expected) in [("integrator", 20), ("creator", 80), ("account1", 450)] {
+            let msg = QueryMsg::GetBalance {
+                account: account.to_owned(),
+                denom: "usei".to_owned(),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: GetBalanceResponse = from_binary(&res).unwrap();
+            assert_eq!(Uint128::new(expected), value.balance);
+        }
+
+        // earnings accumulate per denom across sends
+        let info = mock_info("sender", &coins(500, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg).unwrap();
+
+        let msg = QueryMsg::GetReferralStats {
+            referrer: "integrator".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetReferralStatsResponse = from_binary(&res).unwrap();
+        assert_eq!(vec![coin(2, "uatom"), coin(30, "usei")], value.earnings);
+
+        // only the owner can update the referral share, which is validated like the fees
+        let info = mock_info("sender", &[]);
+        let msg = ExecuteMsg::UpdateReferralShare { referral_share: 5000 };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateReferralShare { referral_share: 10001 };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::InvalidReferralShareError { referral_share: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateReferralShare { referral_share: 5000 };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetConfig {}).unwrap();
+        let value: GetConfigResponse = from_binary(&res).unwrap();
+        assert_eq!(5000, value.referral_share);
+    }
+
+    #[test]
+    fn denom_registry() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // only the owner can register denoms
+        let msg = ExecuteMsg::RegisterDenom {
+            denom: "usei".to_owned(),
+            display_name: "SEI".to_owned(),
+            decimals: 6,
+            min_send: Some(Uint128::new(10)),
+        };
+        let info = mock_info("sender", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let send_msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+
+        // in open mode unregistered denoms can be sent but registered minimums still apply
+        let info = mock_info("sender", &coins(100, "uspam"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+
+        let info = mock_info("sender", &coins(5, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap_err();
+        match res {
+            ContractError::BelowMinimumSendError { denom: _, amount: _, min_send: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // in allowlist mode only registered denoms can be sent
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::SetDenomAllowlist { enabled: true };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("sender", &[coin(100, "usei"), coin(100, "uspam")]);
+        let res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnsupportedDenomError { denom } => assert_eq!("uspam", denom),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // nothing was credited by the rejected send
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.balance);
+
+        let info = mock_info("sender", &coins(100, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send_msg.clone()).unwrap();
+
+        // registered denoms are listed with their metadata
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::RegisterDenom {
+            denom: "uatom".to_owned(),
+            display_name: "ATOM".to_owned(),
+            decimals: 6,
+            min_send: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::ListSupportedDenoms {
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ListSupportedDenomsResponse = from_binary(&res).unwrap();
+        assert!(value.denom_allowlist);
+        assert_eq!(
+            vec![
+                SupportedDenom {
+                    denom: "uatom".to_owned(),
+                    display_name: "ATOM".to_owned(),
+                    decimals: 6,
+                    min_send: None,
+                },
+                SupportedDenom {
+                    denom: "usei".to_owned(),
+                    display_name: "SEI".to_owned(),
+                    decimals: 6,
+                    min_send: Some(Uint128::new(10)),
+                },
+            ],
+            value.denoms
+        );
+
+        // deregistered denoms can no longer be sent in allowlist mode
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::DeregisterDenom {
+            denom: "usei".to_owned(),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("sender", &coins(100, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, send_msg).unwrap_err();
+        match res {
+            ContractError::UnsupportedDenomError { denom: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn escrow() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let recipients = vec![
+            Recipient { address: "account1".to_owned(), weight: 1 },
+            Recipient { address: "account2".to_owned(), weight: 1 },
+        ];
+
+        // the escrow must expire in the future
+        let info = mock_info("sender", &coins(1000, "usei"));
+        let msg = ExecuteMsg::SendEscrow {
+            recipients: recipients.clone(),
+            expires: mock_env().block.time,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::InvalidExpirationError { expires: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the fees are taken up front and the remaining 900 usei are escrowed
+        let expires = mock_env().block.time.plus_seconds(100);
+        let info = mock_info("sender", &coins(1000, "usei"));
+        let msg = ExecuteMsg::SendEscrow { recipients, expires };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(
+            Some("0"),
+            res.attributes
+                .iter()
+                .find(|attr| attr.key == "escrow_id")
+                .map(|attr| attr.value.as_str())
+        );
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetEscrow { id: 0 }).unwrap();
+        let value: EscrowResponse = from_binary(&res).unwrap();
+        assert_eq!(Addr::unchecked("sender"), value.sender);
+        assert_eq!(2, value.shares.len());
+        assert_eq!(coins(450, "usei"), value.shares[0].funds);
+
+        // escrowed funds still count towards the liabilities
+        let msg = QueryMsg::GetSolvency {
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetSolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1000), value.liabilities);
+
+        // the escrow is listed for the sender and for each recipient
+        let msg = QueryMsg::ListEscrowsBySender {
+            sender: "sender".to_owned(),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ListEscrowsResponse = from_binary(&res).unwrap();
+        assert_eq!(vec![0], value.escrows.iter().map(|escrow| escrow.id).collect::<Vec<_>>());
+
+        let msg = QueryMsg::ListEscrowsByRecipient {
+            recipient: "account1".to_owned(),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ListEscrowsResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.escrows.len());
+
+        // the sender cannot refund before the escrow expires
+        let info = mock_info("sender", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Refund { id: 0 }).unwrap_err();
+        match res {
+            ContractError::EscrowNotExpiredError { id: _, expires: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // a recipient claims its share into its balance, only once
+        let info = mock_info("account1", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Claim { id: 0 }).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetBalanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(450), value.balance);
+
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Claim { id: 0 }).unwrap_err();
+        match res {
+            ContractError::NothingToClaimError { id: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let msg = QueryMsg::ListEscrowsByRecipient {
+            recipient: "account1".to_owned(),
+            start_after: None,
+            limit: None,
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: ListEscrowsResponse = from_binary(&res).unwrap();
+        assert_eq!(0, value.escrows.len());
+
+        // once expired the remaining share can no longer be claimed
+        let mut env = mock_env();
+        env.block.time = expires;
+
+        let info = mock_info("account2", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Claim { id: 0 }).unwrap_err();
+        match res {
+            ContractError::EscrowExpiredError { id: _, expires: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // only the sender can refund the unclaimed share
+        let info = mock_info("account2", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Refund { id: 0 }).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("sender", &[]);
+        let _res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Refund { id: 0 }).unwrap();
+
+        let msg = QueryMsg::GetBalance {
+            account: "sender".to_owned(),
             denom: "usei".to_owned(),
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
         let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(0), value.balance);
+        assert_eq!(Uint128::new(450), value.balance);
 
-        // disburse an initial send to two accounts (both accounts should have 4 after fees in their allowances i.e. balances)
-        let info = mock_info("sender", &coins(10, "usei"));
-        let msg: ExecuteMsg = ExecuteMsg::Send {
-            account1: "account1".to_owned(),
-            account2: "account2".to_owned(),
+        // the settled escrow is removed while the liabilities are unchanged
+        let res = query(deps.as_ref(), env.clone(), QueryMsg::GetEscrow { id: 0 });
+        assert!(res.is_err());
+
+        let msg = QueryMsg::GetSolvency {
+            denom: "usei".to_owned(),
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        let value: GetSolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1000), value.liabilities);
+    }
 
-        // query to check updated balance of account 1
+    #[test]
+    fn arbiter_payments() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // the arbiter must be a third party
+        let expires = mock_env().block.time.plus_seconds(100);
+        for arbiter in ["sender", "seller"] {
+            let info = mock_info("sender", &coins(1000, "usei"));
+            let msg = ExecuteMsg::SendWithArbiter {
+                recipient: "seller".to_owned(),
+                arbiter: arbiter.to_owned(),
+                expires,
+            };
+            let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+            match res {
+                ContractError::InvalidArbiterError { arbiter: _ } => (),
+                e => panic!("unexpected error: {:?}", e),
+            }
+        }
+
+        // three pending payments of 1000 usei, with ids 0, 1 and 2
+        for _ in 0..3 {
+            let info = mock_info("sender", &coins(1000, "usei"));
+            let msg = ExecuteMsg::SendWithArbiter {
+                recipient: "seller".to_owned(),
+                arbiter: "arbiter".to_owned(),
+                expires,
+            };
+            let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetArbiterPayment { id: 0 }).unwrap();
+        let value: ArbiterPaymentResponse = from_binary(&res).unwrap();
+        assert_eq!(Addr::unchecked("seller"), value.recipient);
+        assert_eq!(coins(1000, "usei"), value.funds);
+
+        // only the arbiter can approve a payment
+        let info = mock_info("seller", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Approve { id: 0 }).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the fees are only charged once the payment is approved
         let msg = QueryMsg::GetBalance {
-            account: "account1".to_owned(),
+            account: "creator".to_owned(),
             denom: "usei".to_owned(),
         };
         let res = query(deps.as_ref(), mock_env(), msg).unwrap();
         let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(4), value.balance);
+        assert_eq!(Uint128::new(0), value.balance);
+
+        let info = mock_info("arbiter", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Approve { id: 0 }).unwrap();
+
+        for (account, expected) in [("seller", 900), ("creator", 100)] {
+            let msg = QueryMsg::GetBalance {
+                account: account.to_owned(),
+                denom: "usei".to_owned(),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: GetBalanceResponse = from_binary(&res).unwrap();
+            assert_eq!(Uint128::new(expected), value.balance);
+        }
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetArbiterPayment { id: 0 });
+        assert!(res.is_err());
+
+        // a rejected payment is refunded in full
+        let info = mock_info("arbiter", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::Reject { id: 1 }).unwrap();
 
-        // query to check updated balance of account 2
         let msg = QueryMsg::GetBalance {
-            account: "account2".to_owned(),
+            account: "sender".to_owned(),
             denom: "usei".to_owned(),
         };
         let res = query(deps.as_ref(), mock_env(), msg).unwrap();
         let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(5), value.balance);
+        assert_eq!(Uint128::new(1000), value.balance);
 
-        // retrieve the owner to check if fees were collected
-        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetOwner {}).unwrap();
-        let value: GetOwnerResponse = from_binary(&res).unwrap();  
-        let owner: Addr = value.owner;
+        // payments can only be refunded without the arbiter once they have expired
+        let info = mock_info("seller", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::RefundPayment { id: 2 }).unwrap_err();
+        match res {
+            ContractError::PaymentNotExpiredError { id: _, expires: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let mut env = mock_env();
+        env.block.time = expires;
+
+        let info = mock_info("arbiter", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::Approve { id: 2 }).unwrap_err();
+        match res {
+            ContractError::PaymentExpiredError { id: _, expires: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("seller", &[]);
+        let _res = execute(deps.as_mut(), env.clone(), info, ExecuteMsg::RefundPayment { id: 2 }).unwrap();
 
-        // retrieve the balance of the owner to see if fees were collected
         let msg = QueryMsg::GetBalance {
-            account: owner.to_string(),
+            account: "sender".to_owned(),
             denom: "usei".to_owned(),
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
         let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(1), value.balance);
+        assert_eq!(Uint128::new(2000), value.balance);
 
+        // pending payments were counted in the liabilities the whole time
+        let msg = QueryMsg::GetSolvency {
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        let value: GetSolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(3000), value.liabilities);
     }
 
     #[test]
-    fn send_multiple() {
+    fn vesting() {
         let mut deps = mock_dependencies();
 
-        let msg = InstantiateMsg { fees: 10 };
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
 
         // instantiate the contract
         let info = mock_info("creator", &coins(0, "usei"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // disburse an initial send to two accounts
-        let info = mock_info("sender", &coins(51, "usei"));
-        let msg: ExecuteMsg = ExecuteMsg::Send {
+        let start = mock_env().block.time;
+        let cliff = start.plus_seconds(100);
+        let end = start.plus_seconds(1000);
+
+        // a schedule whose cliff is after its end is rejected
+        let info = mock_info("sender", &coins(2000, "usei"));
+        let msg = ExecuteMsg::SendVesting {
             account1: "account1".to_owned(),
             account2: "account2".to_owned(),
+            start,
+            cliff: end.plus_seconds(1),
+            end,
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::InvalidVestingScheduleError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // each account is credited 900 usei vesting over 1000 seconds
+        let info = mock_info("sender", &coins(2000, "usei"));
+        let msg = ExecuteMsg::SendVesting {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            start,
+            cliff,
+            end,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // query to check updated balance of account 1
         let msg = QueryMsg::GetBalance {
             account: "account1".to_owned(),
             denom: "usei".to_owned(),
         };
         let res = query(deps.as_ref(), mock_env(), msg).unwrap();
         let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(23), value.balance);
+        assert_eq!(Uint128::new(900), value.balance);
 
-        // query to check updated balance of account 2
-        let msg = QueryMsg::GetBalance {
-            account: "account2".to_owned(),
+        // nothing can be withdrawn before the cliff
+        let mut env = mock_env();
+        env.block.time = start.plus_seconds(50);
+
+        let info = mock_info("account1", &[]);
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::new(1),
             denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(23), value.balance);
+        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        match res {
+            ContractError::UnvestedBalanceError { denom: _, withdrawable: _, requested: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
 
-        // disburse an another send to two accounts (both accounts should have 5 in their allowances i.e. balances)
-        let info = mock_info("sender", &coins(65, "usei"));
-        let msg: ExecuteMsg = ExecuteMsg::Send {
-            account1: "account1".to_owned(),
-            account2: "account3".to_owned(),
+        // half way through the schedule, half of the balance is vested
+        let mut env = mock_env();
+        env.block.time = start.plus_seconds(500);
+
+        let msg = QueryMsg::GetWithdrawable {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: GetWithdrawableResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(900), value.balance);
+        assert_eq!(Uint128::new(450), value.locked);
+        assert_eq!(Uint128::new(450), value.withdrawable);
 
-        // query to check updated balance of account 1
-        let msg = QueryMsg::GetBalance {
+        let msg = QueryMsg::GetVestingSchedule {
             account: "account1".to_owned(),
             denom: "usei".to_owned(),
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(52), value.balance);
+        let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+        let value: GetVestingScheduleResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.schedules.len());
+        assert_eq!(Addr::unchecked("sender"), value.schedules[0].sender);
+        assert_eq!(Uint128::new(900), value.schedules[0].total);
+        assert_eq!(Uint128::new(450), value.vested);
+        assert_eq!(Uint128::new(450), value.locked);
 
-        // query to check updated balance of account 3
-        let msg = QueryMsg::GetBalance {
-            account: "account3".to_owned(),
+        let info = mock_info("account1", &[]);
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::new(451),
             denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(30), value.balance);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap_err();
+        match res {
+            ContractError::UnvestedBalanceError { denom: _, withdrawable: _, requested: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // withdrawing everything only withdraws the vested portion
+        let info = mock_info("account1", &[]);
+        let msg = ExecuteMsg::WithdrawAll {
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert_eq!(
+            res.messages[0].msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "account1".to_owned(),
+                amount: coins(450, "usei"),
+            })
+        );
+
+        // unvested balances still count towards the liabilities
+        let msg = QueryMsg::GetSolvency {
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        let value: GetSolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(1550), value.liabilities);
+
+        // the rest can be withdrawn once the schedule has ended, which removes it
+        let mut env = mock_env();
+        env.block.time = end;
+
+        let info = mock_info("account1", &[]);
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::new(450),
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let msg = QueryMsg::GetVestingSchedule {
+            account: "account1".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), env, msg).unwrap();
+        let value: GetVestingScheduleResponse = from_binary(&res).unwrap();
+        assert!(value.schedules.is_empty());
+
+        // the number of schedules a sender can lock a balance with is capped so withdrawals stay cheap
+        let msg = ExecuteMsg::SendVesting {
+            account1: "victim".to_owned(),
+            account2: "account3".to_owned(),
+            start,
+            cliff,
+            end,
+        };
+        for _ in 0..MAX_VESTING_SCHEDULES {
+            let info = mock_info("attacker", &coins(20, "usei"));
+            let _res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+        }
+
+        let info = mock_info("attacker", &coins(20, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::TooManyVestingSchedulesError { address, denom: _, sender, max: _ } => {
+                assert_eq!("victim", address);
+                assert_eq!("attacker", sender);
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the schedules of one sender do not use up the schedules of another
+        let info = mock_info("sender", &coins(20, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap();
+
+        let msg_query = QueryMsg::GetVestingSchedule {
+            account: "victim".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg_query.clone()).unwrap();
+        let value: GetVestingScheduleResponse = from_binary(&res).unwrap();
+        assert_eq!(MAX_VESTING_SCHEDULES as usize + 1, value.schedules.len());
+
+        // fully vested schedules no longer count towards the cap and are removed when a new one is added
+        let mut env = mock_env();
+        env.block.time = end;
+
+        let info = mock_info("attacker", &coins(20, "usei"));
+        let msg = ExecuteMsg::SendVesting {
+            account1: "victim".to_owned(),
+            account2: "account3".to_owned(),
+            start: end,
+            cliff: end,
+            end: end.plus_seconds(1000),
+        };
+        let _res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), env, msg_query).unwrap();
+        let value: GetVestingScheduleResponse = from_binary(&res).unwrap();
+        assert_eq!(1, value.schedules.len());
+        assert_eq!(Addr::unchecked("attacker"), value.schedules[0].sender);
+
+        // registered denoms require each schedule to lock at least the minimum send amount
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::RegisterDenom {
+            denom: "uatom".to_owned(),
+            display_name: "ATOM".to_owned(),
+            decimals: 6,
+            min_send: Some(Uint128::new(100)),
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("sender", &coins(150, "uatom"));
+        let msg = ExecuteMsg::SendVesting {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            start,
+            cliff,
+            end,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::BelowMinimumSendError { denom: _, amount, min_send: _ } => {
+                assert_eq!(Uint128::new(67), amount)
+            }
+            e => panic!("unexpected error: {:?}", e),
+        }
     }
 
     #[test]
-    fn send_multiple_currencies() {
+    fn send_cw20_denom_fail() {
         let mut deps = mock_dependencies();
 
-        let msg = InstantiateMsg { fees: 10 };
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
 
         // instantiate the contract
         let info = mock_info("creator", &coins(0, "usei"));
-        let _res = instantiate(deps.as_mut(), mock_env(), info, msg);
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // disburse an initial send to two accounts
-        let info = mock_info("sender", &coins(100, "usei"));
+        // a native coin using the CW20 denom prefix would be withdrawable as the CW20 token
+        let info = mock_info("sender", &coins(100, "cw20:token"));
         let msg: ExecuteMsg = ExecuteMsg::Send {
             account1: "account1".to_owned(),
             account2: "account2".to_owned(),
+            referrer: None,
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::InvalidNativeDenomError { denom: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
 
-        // query to check updated balance of account 1
-        let msg = QueryMsg::GetBalance {
-            account: "account1".to_owned(),
-            denom: "usei".to_owned(),
+    #[test]
+    fn receive_native_funds_fail() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(45), value.balance);
 
-        // query to check updated balance of account 2
-        let msg = QueryMsg::GetBalance {
-            account: "account2".to_owned(),
-            denom: "usei".to_owned(),
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // native coins attached to a receive would be left in the contract without being credited
+        let info = mock_info("token", &coins(100, "usei"));
+        let msg = ExecuteMsg::Receive(cw20::Cw20ReceiveMsg {
+            sender: "sender".to_owned(),
+            amount: Uint128::new(100),
+            msg: to_binary(&ReceiveMsg::Send {
+                account1: "account1".to_owned(),
+                account2: "account2".to_owned(),
+                referrer: None,
+            })
+            .unwrap(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::Payment(_) => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn pause() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: Some("pauser".to_owned()),
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(45), value.balance);
 
-        // disburse an another send to two accounts but with a different currency this time
-        let info = mock_info("sender", &coins(50, "wei"));
-        let msg: ExecuteMsg = ExecuteMsg::Send {
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // disburse an initial send to two accounts
+        let send = ExecuteMsg::Send {
             account1: "account1".to_owned(),
             account2: "account2".to_owned(),
+            referrer: None,
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+        let info = mock_info("sender", &coins(100, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap();
 
-        // query to check updated balance of account 1
-        let msg = QueryMsg::GetBalance {
-            account: "account1".to_owned(),
-            denom: "wei".to_owned(),
+        // only the owner or the pauser can pause
+        let msg = ExecuteMsg::Pause {
+            deposits: true,
+            withdrawals: false,
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(22), value.balance);
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg.clone()).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
 
-        // query to check updated balance of account 3
-        let msg = QueryMsg::GetBalance {
-            account: "account2".to_owned(),
-            denom: "wei".to_owned(),
+        // the pauser pauses deposits only
+        let info = mock_info("pauser", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let res = query(deps.as_ref(), mock_env(), QueryMsg::GetPauseStatus {}).unwrap();
+        let value: GetPauseStatusResponse = from_binary(&res).unwrap();
+        assert!(value.deposits_paused);
+        assert!(!value.withdrawals_paused);
+        assert_eq!(Some(Addr::unchecked("pauser")), value.pauser);
+
+        let info = mock_info("sender", &coins(100, "usei"));
+        let res = execute(deps.as_mut(), mock_env(), info, send.clone()).unwrap_err();
+        match res {
+            ContractError::PausedError { action: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // users can still exit while deposits are paused
+        let withdraw = ExecuteMsg::Withdraw {
+            amount: Uint128::new(20),
+            denom: "usei".to_owned(),
+            recipient: None,
+            msg: None,
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(23), value.balance);
+        let info = mock_info("account1", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, withdraw.clone()).unwrap();
+
+        // the owner pauses withdrawals as well
+        let msg = ExecuteMsg::Pause {
+            deposits: false,
+            withdrawals: true,
+        };
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, withdraw.clone()).unwrap_err();
+        match res {
+            ContractError::PausedError { action: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // unpausing everything restores both
+        let msg = ExecuteMsg::Unpause {
+            deposits: true,
+            withdrawals: true,
+        };
+        let info = mock_info("pauser", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let info = mock_info("account1", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, withdraw).unwrap();
+        let info = mock_info("sender", &coins(100, "usei"));
+        let _res = execute(deps.as_mut(), mock_env(), info, send).unwrap();
+
+        // once the pauser is removed it can no longer pause
+        let info = mock_info("creator", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::UpdatePauser { pauser: None }).unwrap();
+
+        let msg = ExecuteMsg::Pause {
+            deposits: true,
+            withdrawals: true,
+        };
+        let info = mock_info("pauser", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::UnauthorizedError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
     }
 
     #[test]
-    fn withdraw_basic() {
+    fn withdraw_many() {
         let mut deps = mock_dependencies();
 
-        let msg = InstantiateMsg { fees: 10 };
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
 
         // instantiate the contract
         let info = mock_info("creator", &coins(0, "usei"));
         let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // disburse an initial send to two accounts
-        let info = mock_info("sender", &coins(100, "usei"));
+        // disburse a send in three currencies to two accounts
+        let info = mock_info("sender", &[coin(100, "uatom"), coin(100, "usei"), coin(50, "wei")]);
         let msg: ExecuteMsg = ExecuteMsg::Send {
             account1: "account1".to_owned(),
             account2: "account2".to_owned(),
+            referrer: None,
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // over-withdrawing any one denom fails the whole message and names the denom
+        let msg = ExecuteMsg::WithdrawMany {
+            coins: vec![coin(45, "uatom"), coin(23, "wei")],
+        };
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::InsufficientBalanceError {
+                denom,
+                balance: _,
+                requested: _,
+            } => assert_eq!("wei", denom),
+            e => panic!("unexpected error: {:?}", e),
+        }
 
-        // query to check updated balance of account 1
         let msg = QueryMsg::GetBalance {
             account: "account1".to_owned(),
-            denom: "usei".to_owned(),
+            denom: "uatom".to_owned(),
         };
         let res = query(deps.as_ref(), mock_env(), msg).unwrap();
         let value: GetBalanceResponse = from_binary(&res).unwrap();
         assert_eq!(Uint128::new(45), value.balance);
 
-        // account 1 withdraws money from the contract
-        let msg = ExecuteMsg::Withdraw {
-            amount: Uint128::new(25),
-            denom: "usei".to_owned(),
+        // all coins are paid out in a single bank send
+        let msg = ExecuteMsg::WithdrawMany {
+            coins: vec![coin(45, "uatom"), coin(20, "wei")],
         };
-        let info = mock_info("account1", &coins(0, "usei"));
+        let info = mock_info("account1", &[]);
         let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
         assert_eq!(1, res.messages.len());
-        let msg = res.messages.get(0).expect("no message");
+        let msg = res.messages.first().expect("no message");
         assert_eq!(
             msg.msg,
             CosmosMsg::Bank(BankMsg::Send {
                 to_address: "account1".to_owned(),
-                amount: coins(25, "usei")
+                amount: vec![coin(45, "uatom"), coin(20, "wei")]
             })
         );
 
-        // query to check updated balance for account 1
-        let msg = QueryMsg::GetBalance {
+        // withdrawing everything pays out the remaining balance of every denom
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawEverything {}).unwrap();
+        assert_eq!(1, res.messages.len());
+        let msg = res.messages.first().expect("no message");
+        assert_eq!(
+            msg.msg,
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "account1".to_owned(),
+                amount: vec![coin(45, "usei"), coin(2, "wei")]
+            })
+        );
+
+        let msg = QueryMsg::GetAllBalances {
             account: "account1".to_owned(),
-            denom: "usei".to_owned(),
+            start_after: None,
+            limit: None,
         };
         let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(20), value.balance);
+        let value: GetAllBalancesResponse = from_binary(&res).unwrap();
+        assert!(value.balances.is_empty());
 
-        // query to check updated balance for account 2
-        let msg = QueryMsg::GetBalance {
-            account: "account2".to_owned(),
-            denom: "usei".to_owned(),
+        // withdrawing nothing is rejected rather than succeeding without sending anything
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, ExecuteMsg::WithdrawEverything {}).unwrap_err();
+        match res {
+            ContractError::NothingToWithdrawError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("account2", &[]);
+        let msg = ExecuteMsg::WithdrawMany { coins: vec![] };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::NothingToWithdrawError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        let info = mock_info("account2", &[]);
+        let msg = ExecuteMsg::WithdrawMany {
+            coins: vec![coin(10, "uatom"), coin(0, "wei")],
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(45), value.balance);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::ZeroAmountError { denom } => assert_eq!("wei", denom),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // repeated denoms adding up past the maximum amount fail instead of overflowing
+        let info = mock_info("account2", &[]);
+        let msg = ExecuteMsg::WithdrawMany {
+            coins: vec![coin(u128::MAX, "uatom"), coin(1, "uatom")],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::Std(StdError::Overflow { .. }) => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
     }
 
     #[test]
-    fn withdraw_all() {
+    fn withdraw_to_recipient() {
         let mut deps = mock_dependencies();
 
-        let msg = InstantiateMsg { fees: 10 };
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
 
         // instantiate the contract
         let info = mock_info("creator", &coins(0, "usei"));
@@ -521,58 +5985,87 @@ mod tests {
         let msg: ExecuteMsg = ExecuteMsg::Send {
             account1: "account1".to_owned(),
             account2: "account2".to_owned(),
+            referrer: None,
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // query to check updated balance of account 1
-        let msg = QueryMsg::GetBalance {
-            account: "account1".to_owned(),
+        // the recipient address must be valid
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::new(25),
             denom: "usei".to_owned(),
+            recipient: Some("CW".to_owned()),
+            msg: None,
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(45), value.balance);
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::Std(_) => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
 
-        // account 1 withdraws money from the contract
-        let msg = ExecuteMsg::WithdrawAll {
+        // account 1 withdraws to its cold wallet
+        let msg = ExecuteMsg::Withdraw {
+            amount: Uint128::new(25),
             denom: "usei".to_owned(),
+            recipient: Some("cold_wallet".to_owned()),
+            msg: None,
         };
-        let info = mock_info("account1", &coins(0, "usei"));
+        let info = mock_info("account1", &[]);
         let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(1, res.messages.len());
-        let msg = res.messages.get(0).expect("no message");
+        let msg = res.messages.first().expect("no message");
         assert_eq!(
             msg.msg,
             CosmosMsg::Bank(BankMsg::Send {
-                to_address: "account1".to_owned(),
-                amount: coins(45, "usei")
+                to_address: "cold_wallet".to_owned(),
+                amount: coins(25, "usei")
             })
         );
+        assert!(res.attributes.iter().any(|a| a.key == "withdraw" && a.value == "account1"));
+        assert!(res.attributes.iter().any(|a| a.key == "recipient" && a.value == "cold_wallet"));
 
-        // query to check updated balance for account 1
-        let msg = QueryMsg::GetBalance {
-            account: "account1".to_owned(),
+        // account 1 withdraws the rest straight into another contract
+        let msg = ExecuteMsg::WithdrawAll {
             denom: "usei".to_owned(),
+            recipient: Some("vault".to_owned()),
+            msg: Some(Binary::from(br#"{"deposit":{}}"#)),
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(0), value.balance);
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        let msg = res.messages.first().expect("no message");
+        assert_eq!(
+            msg.msg,
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "vault".to_owned(),
+                msg: Binary::from(br#"{"deposit":{}}"#),
+                funds: coins(20, "usei")
+            })
+        );
 
-        // query to check updated balance for account 2
+        // the debited account is account 1, not the recipient
         let msg = QueryMsg::GetBalance {
-            account: "account2".to_owned(),
+            account: "account1".to_owned(),
             denom: "usei".to_owned(),
         };
         let res = query(deps.as_ref(), mock_env(), msg).unwrap();
         let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(45), value.balance);
+        assert_eq!(Uint128::new(0), value.balance);
     }
 
     #[test]
-    fn withdraw_fail() {
+    fn transfer_balance() {
         let mut deps = mock_dependencies();
 
-        let msg = InstantiateMsg { fees: 10 };
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
 
         // instantiate the contract
         let info = mock_info("creator", &coins(0, "usei"));
@@ -583,57 +6076,71 @@ mod tests {
         let msg: ExecuteMsg = ExecuteMsg::Send {
             account1: "account1".to_owned(),
             account2: "account2".to_owned(),
+            referrer: None,
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg);
-
-        // query to check updated balance of account 1
-        let msg = QueryMsg::GetBalance {
-            account: "account1".to_owned(),
-            denom: "usei".to_owned(),
-        };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(45), value.balance);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // account 1 over-withdraws money from the contract
-        let msg = ExecuteMsg::Withdraw {
+        // account 1 cannot transfer more than its balance
+        let msg = ExecuteMsg::TransferBalance {
+            to: "account3".to_owned(),
             amount: Uint128::new(46),
             denom: "usei".to_owned(),
         };
-        let info = mock_info("account1", &coins(0, "usei"));
+        let info = mock_info("account1", &[]);
         let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
         match res {
             ContractError::InsufficientBalanceError {
+                denom: _,
                 balance: _,
                 requested: _,
             } => (),
             e => panic!("unexpected error: {:?}", e),
         }
 
-        // query to check updated balance for account 1
-        let msg = QueryMsg::GetBalance {
-            account: "account1".to_owned(),
+        // account 1 moves 40 usei to account 3 without sending any funds or paying fees
+        let msg = ExecuteMsg::TransferBalance {
+            to: "account3".to_owned(),
+            amount: Uint128::new(40),
             denom: "usei".to_owned(),
         };
-        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(45), value.balance);
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert_eq!(0, res.messages.len());
 
-        // query to check updated balance for account 2
-        let msg = QueryMsg::GetBalance {
-            account: "account2".to_owned(),
+        for (account, expected) in [("account1", 5), ("account3", 40), ("creator", 10)] {
+            let msg = QueryMsg::GetBalance {
+                account: account.to_owned(),
+                denom: "usei".to_owned(),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: GetBalanceResponse = from_binary(&res).unwrap();
+            assert_eq!(Uint128::new(expected), value.balance);
+        }
+
+        // the total owed by the contract is unchanged
+        let msg = QueryMsg::GetSolvency {
             denom: "usei".to_owned(),
         };
         let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(45), value.balance);
+        let value: GetSolvencyResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(100), value.liabilities);
     }
 
     #[test]
-    fn withdraw_multiple() {
+    fn transfer_balance_with_fees() {
         let mut deps = mock_dependencies();
 
-        let msg = InstantiateMsg { fees: 10 };
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: Some(true),
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
 
         // instantiate the contract
         let info = mock_info("creator", &coins(0, "usei"));
@@ -644,59 +6151,291 @@ mod tests {
         let msg: ExecuteMsg = ExecuteMsg::Send {
             account1: "account1".to_owned(),
             account2: "account2".to_owned(),
+            referrer: None,
         };
-        let _res = execute(deps.as_mut(), mock_env(), info, msg);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // account 1 withdraws money from the contract
-        let msg = ExecuteMsg::Withdraw {
-            amount: Uint128::new(25),
+        // the owner takes 10% of the 40 usei transferred
+        let msg = ExecuteMsg::TransferBalance {
+            to: "account3".to_owned(),
+            amount: Uint128::new(40),
             denom: "usei".to_owned(),
         };
-        let info = mock_info("account1", &coins(0, "usei"));
-        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(1, res.messages.len());
-        let msg = res.messages.get(0).expect("no message");
-        assert_eq!(
-            msg.msg,
-            CosmosMsg::Bank(BankMsg::Send {
-                to_address: "account1".to_owned(),
-                amount: coins(25, "usei")
-            })
-        );
+        let info = mock_info("account1", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
 
-        // query to check updated balance for account 1
-        let msg = QueryMsg::GetBalance {
-            account: "account1".to_owned(),
+        for (account, expected) in [("account1", 5), ("account3", 36), ("creator", 14)] {
+            let msg = QueryMsg::GetBalance {
+                account: account.to_owned(),
+                denom: "usei".to_owned(),
+            };
+            let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+            let value: GetBalanceResponse = from_binary(&res).unwrap();
+            assert_eq!(Uint128::new(expected), value.balance);
+        }
+
+        // a queued fee update applies to transfers once its delay has passed
+        let info = mock_info("creator", &[]);
+        let msg = ExecuteMsg::UpdateFees { fees: 5000 };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(DEFAULT_FEE_UPDATE_DELAY);
+
+        let msg = ExecuteMsg::TransferBalance {
+            to: "account1".to_owned(),
+            amount: Uint128::new(20),
+            denom: "usei".to_owned(),
+        };
+        let info = mock_info("account3", &[]);
+        let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+        assert_eq!("fee_update_activated", res.events[0].ty);
+
+        for (account, expected) in [("account1", 15), ("account3", 16), ("creator", 24)] {
+            let msg = QueryMsg::GetBalance {
+                account: account.to_owned(),
+                denom: "usei".to_owned(),
+            };
+            let res = query(deps.as_ref(), env.clone(), msg).unwrap();
+            let value: GetBalanceResponse = from_binary(&res).unwrap();
+            assert_eq!(Uint128::new(expected), value.balance);
+        }
+    }
+
+    #[test]
+    fn allowances() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // disburse an initial send to two accounts
+        let info = mock_info("sender", &coins(100, "usei"));
+        let msg: ExecuteMsg = ExecuteMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        };
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // account 1 lets the custodian withdraw up to 30 usei within 100 blocks
+        let expires = Expiration::AtHeight(mock_env().block.height + 100);
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: "custodian".to_owned(),
+            denom: "usei".to_owned(),
+            amount: Uint128::new(30),
+            expires: Some(expires),
+        };
+        let info = mock_info("account1", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::GetAllowance {
+            owner: "account1".to_owned(),
+            spender: "custodian".to_owned(),
             denom: "usei".to_owned(),
         };
         let res = query(deps.as_ref(), mock_env(), msg).unwrap();
-        let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(20), value.balance);
+        let value: GetAllowanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(30), value.amount);
+        assert_eq!(expires, value.expires);
 
-        // account 1 withdraws money from the contract a second time
-        let msg = ExecuteMsg::Withdraw {
-            amount: Uint128::new(19),
+        // the custodian cannot withdraw more than its allowance
+        let msg = ExecuteMsg::WithdrawFrom {
+            owner: "account1".to_owned(),
+            amount: Uint128::new(31),
             denom: "usei".to_owned(),
+            recipient: None,
         };
-        let info = mock_info("account1", &coins(0, "usei"));
+        let info = mock_info("custodian", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::InsufficientAllowanceError {
+                denom: _,
+                allowance: _,
+                requested: _,
+            } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // the custodian withdraws 20 usei to the user's cold wallet
+        let msg = ExecuteMsg::WithdrawFrom {
+            owner: "account1".to_owned(),
+            amount: Uint128::new(20),
+            denom: "usei".to_owned(),
+            recipient: Some("cold_wallet".to_owned()),
+        };
+        let info = mock_info("custodian", &[]);
         let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
-        assert_eq!(1, res.messages.len());
-        let msg = res.messages.get(0).expect("no message");
+        let msg = res.messages.first().expect("no message");
         assert_eq!(
             msg.msg,
             CosmosMsg::Bank(BankMsg::Send {
-                to_address: "account1".to_owned(),
-                amount: coins(19, "usei")
+                to_address: "cold_wallet".to_owned(),
+                amount: coins(20, "usei")
             })
         );
 
-        // query to check updated balance for account 1
         let msg = QueryMsg::GetBalance {
             account: "account1".to_owned(),
             denom: "usei".to_owned(),
         };
         let res = query(deps.as_ref(), mock_env(), msg).unwrap();
         let value: GetBalanceResponse = from_binary(&res).unwrap();
-        assert_eq!(Uint128::new(1), value.balance);
+        assert_eq!(Uint128::new(25), value.balance);
+
+        // the remaining allowance can no longer be used once it expires
+        let mut env = mock_env();
+        env.block.height += 100;
+        let msg = ExecuteMsg::WithdrawFrom {
+            owner: "account1".to_owned(),
+            amount: Uint128::new(5),
+            denom: "usei".to_owned(),
+            recipient: None,
+        };
+        let info = mock_info("custodian", &[]);
+        let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+        match res {
+            ContractError::AllowanceExpiredError { denom: _, expires: _ } => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // decreasing the allowance to zero removes it
+        let msg = ExecuteMsg::DecreaseAllowance {
+            spender: "custodian".to_owned(),
+            denom: "usei".to_owned(),
+            amount: Uint128::new(50),
+            expires: None,
+        };
+        let info = mock_info("account1", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = QueryMsg::GetAllowance {
+            owner: "account1".to_owned(),
+            spender: "custodian".to_owned(),
+            denom: "usei".to_owned(),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: GetAllowanceResponse = from_binary(&res).unwrap();
+        assert_eq!(Uint128::new(0), value.amount);
+
+        // increasing an allowance past the maximum amount fails instead of overflowing
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: "custodian".to_owned(),
+            denom: "usei".to_owned(),
+            amount: Uint128::MAX,
+            expires: None,
+        };
+        let info = mock_info("account1", &[]);
+        let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: "custodian".to_owned(),
+            denom: "usei".to_owned(),
+            amount: Uint128::new(1),
+            expires: None,
+        };
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::Std(StdError::Overflow { .. }) => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn query_all_allowances() {
+        let mut deps = mock_dependencies();
+
+        let msg = InstantiateMsg {
+            fees: 1000,
+            fee_update_delay: None,
+            max_recipients: None,
+            pauser: None,
+            charge_transfer_fees: None,
+            fee_recipients: None,
+            fee_recipient: None,
+            referral_share: None,
+            denom_allowlist: None,
+        };
+
+        // instantiate the contract
+        let info = mock_info("creator", &coins(0, "usei"));
+        let _res = instantiate(deps.as_mut(), mock_env(), info, msg).unwrap();
+
+        // an account cannot give itself an allowance
+        let msg = ExecuteMsg::IncreaseAllowance {
+            spender: "account1".to_owned(),
+            denom: "usei".to_owned(),
+            amount: Uint128::new(10),
+            expires: None,
+        };
+        let info = mock_info("account1", &[]);
+        let res = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        match res {
+            ContractError::CannotSetOwnAllowanceError {} => (),
+            e => panic!("unexpected error: {:?}", e),
+        }
+
+        // account 1 gives three allowances
+        for (spender, denom) in [("custodian", "usei"), ("custodian", "wei"), ("exchange", "usei")] {
+            let msg = ExecuteMsg::IncreaseAllowance {
+                spender: spender.to_owned(),
+                denom: denom.to_owned(),
+                amount: Uint128::new(10),
+                expires: None,
+            };
+            let info = mock_info("account1", &[]);
+            let _res = execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        }
+
+        let msg = QueryMsg::AllAllowances {
+            owner: "account1".to_owned(),
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let value: AllAllowancesResponse = from_binary(&res).unwrap();
+        assert_eq!(2, value.allowances.len());
+
+        // the next page starts after the last allowance of the first one
+        let last = value.allowances.last().unwrap();
+        let msg = QueryMsg::AllAllowances {
+            owner: "account1".to_owned(),
+            start_after: Some((last.spender.to_string(), last.denom.clone())),
+            limit: Some(2),
+        };
+        let res = query(deps.as_ref(), mock_env(), msg).unwrap();
+        let next: AllAllowancesResponse = from_binary(&res).unwrap();
+        assert_eq!(1, next.allowances.len());
+
+        // together the pages hold every allowance exactly once
+        let mut all: Vec<(String, String)> = value
+            .allowances
+            .into_iter()
+            .chain(next.allowances)
+            .map(|a| (a.spender.to_string(), a.denom))
+            .collect();
+        all.sort();
+        assert_eq!(
+            vec![
+                ("custodian".to_owned(), "usei".to_owned()),
+                ("custodian".to_owned(), "wei".to_owned()),
+                ("exchange".to_owned(), "usei".to_owned()),
+            ],
+            all
+        );
     }
 }
@@ -1,4 +1,5 @@
-use cosmwasm_std::{StdError, Uint128};
+use cosmwasm_std::{StdError, Timestamp, Uint128};
+use cw_utils::{Expiration, PaymentError};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -6,9 +7,120 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
-    #[error("Insufficient Balance Error: your balance - {balance:?} - is less than the requested amount - {requested:?}")]
-    InsufficientBalanceError { balance: Uint128, requested: Uint128 },
+    #[error("{0}")]
+    Payment(#[from] PaymentError),
+
+    #[error("Insufficient Balance Error: your balance of {denom} - {balance:?} - is less than the requested amount - {requested:?}")]
+    InsufficientBalanceError { denom: String, balance: Uint128, requested: Uint128 },
+
+    #[error("Invalid Fee Percentage: the entered fee parameter must be at most 10000 basis points - {fees:?}.")]
+    InvalidFeePercentageError { fees: u16 },
+
+    #[error("Unauthorized Error: the sender is not permitted to perform this action.")]
+    UnauthorizedError {},
+
+    #[error("No Pending Owner Error: there is no ownership transfer currently proposed.")]
+    NoPendingOwnerError {},
+
+    #[error("Ownership Proposal Expired Error: the ownership transfer proposal has expired - {expires}.")]
+    OwnershipProposalExpiredError { expires: Expiration },
+
+    #[error("Invalid Contract Name Error: cannot migrate from contract {actual} - expected {expected}.")]
+    InvalidContractNameError { expected: String, actual: String },
+
+    #[error("Invalid Version Error: the contract version is not a valid semantic version - {version}.")]
+    InvalidVersionError { version: String },
+
+    #[error("Migration Downgrade Error: the stored version - {stored} - is newer than the current version - {current}.")]
+    MigrationDowngradeError { stored: String, current: String },
+
+    #[error("No Recipients Error: at least one recipient must be provided.")]
+    NoRecipientsError {},
+
+    #[error("Too Many Recipients Error: the number of recipients - {count} - is more than the maximum - {max}.")]
+    TooManyRecipientsError { count: u32, max: u32 },
+
+    #[error("Zero Weight Error: the weight of a recipient must be greater than zero - {address}.")]
+    ZeroWeightError { address: String },
+
+    #[error("Duplicate Recipient Error: a recipient may only be listed once - {address}.")]
+    DuplicateRecipientError { address: String },
+
+    #[error("No Surplus Error: the contract does not hold more than it owes to users - {denom}.")]
+    NoSurplusError { denom: String },
+
+    #[error("Invalid Native Denom Error: native coins cannot use the CW20 denom prefix - {denom}.")]
+    InvalidNativeDenomError { denom: String },
+
+    #[error("Paused Error: {action} are currently paused.")]
+    PausedError { action: String },
+
+    #[error("Cannot Set Own Allowance Error: the spender cannot be the owner of the balance.")]
+    CannotSetOwnAllowanceError {},
+
+    #[error("Invalid Expiration Error: the allowance expiry has already passed - {expires}.")]
+    InvalidExpirationError { expires: Expiration },
+
+    #[error("Allowance Expired Error: the allowance to withdraw {denom} has expired - {expires}.")]
+    AllowanceExpiredError { denom: String, expires: Expiration },
+
+    #[error("Insufficient Allowance Error: your allowance of {denom} - {allowance:?} - is less than the requested amount - {requested:?}")]
+    InsufficientAllowanceError { denom: String, allowance: Uint128, requested: Uint128 },
+
+    #[error("Invalid Fee Bounds Error: the minimum fee - {min_fee} - is more than the maximum fee - {max_fee}.")]
+    InvalidFeeBoundsError { min_fee: Uint128, max_fee: Uint128 },
+
+    #[error("Below Minimum Fee Error: the amount of {denom} sent - {amount} - cannot cover the minimum fee - {min_fee}.")]
+    BelowMinimumFeeError { denom: String, amount: Uint128, min_fee: Uint128 },
+
+    #[error("Invalid Referral Share Error: the referral share must be at most 10000 basis points - {referral_share}.")]
+    InvalidReferralShareError { referral_share: u16 },
+
+    #[error("Self Referral Error: the sender cannot be its own referrer.")]
+    SelfReferralError {},
+
+    #[error("Unsupported Denom Error: only registered denoms can be sent - {denom}.")]
+    UnsupportedDenomError { denom: String },
+
+    #[error("Below Minimum Send Error: the amount of {denom} sent - {amount} - is less than the minimum - {min_send}.")]
+    BelowMinimumSendError { denom: String, amount: Uint128, min_send: Uint128 },
+
+    #[error("No Funds Error: a send must include funds.")]
+    NoFundsError {},
+
+    #[error("Zero Amount Error: the amount must be greater than zero - {denom}.")]
+    ZeroAmountError { denom: String },
+
+    #[error("Escrow Expired Error: escrow {id} can no longer be claimed - {expires}.")]
+    EscrowExpiredError { id: u64, expires: Timestamp },
+
+    #[error("Escrow Not Expired Error: escrow {id} cannot be refunded before it expires - {expires}.")]
+    EscrowNotExpiredError { id: u64, expires: Timestamp },
+
+    #[error("Nothing To Claim Error: the sender has no unclaimed share of escrow {id}.")]
+    NothingToClaimError { id: u64 },
+
+    #[error("Payment Expired Error: payment {id} can no longer be approved - {expires}.")]
+    PaymentExpiredError { id: u64, expires: Timestamp },
+
+    #[error("Payment Not Expired Error: payment {id} cannot be refunded before it expires - {expires}.")]
+    PaymentNotExpiredError { id: u64, expires: Timestamp },
+
+    #[error("Invalid Vesting Schedule Error: the schedule must satisfy start <= cliff <= end and start < end.")]
+    InvalidVestingScheduleError {},
+
+    #[error("Unvested Balance Error: your vested balance of {denom} - {withdrawable:?} - is less than the requested amount - {requested:?}")]
+    UnvestedBalanceError { denom: String, withdrawable: Uint128, requested: Uint128 },
+
+    #[error("Conflicting Fee Recipients Error: a fee recipient cannot be set while the fees are split between weighted fee recipients.")]
+    ConflictingFeeRecipientsError {},
+
+    #[error("Invalid Arbiter Error: the arbiter must be a third party other than the sender and the recipient - {arbiter}.")]
+    InvalidArbiterError { arbiter: String },
+
+    #[error("Too Many Vesting Schedules Error: the balance of {denom} of {address} is already locked by the maximum number of vesting schedules from {sender} - {max}.")]
+    TooManyVestingSchedulesError { address: String, denom: String, sender: String, max: u32 },
 
-    #[error("Invalid Fee Percentage: the enter fee parameter must be less than 100 - {fees:?}.")]
-    InvalidFeePercentageError { fees: u8 },
+    #[error("Nothing To Withdraw Error: a withdrawal must include at least one coin.")]
+    NothingToWithdrawError {},
 }
@@ -0,0 +1,196 @@
+use cosmwasm_std::{to_binary, Addr, Empty, Uint128};
+use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg};
+use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+
+use crate::msg::{
+    ExecuteMsg, GetBalanceResponse, GetSolvencyResponse, InstantiateMsg, QueryMsg, ReceiveMsg,
+    Recipient,
+};
+
+const OWNER: &str = "owner";
+const SENDER: &str = "sender";
+
+fn transfer_contract() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        crate::contract::execute,
+        crate::contract::instantiate,
+        crate::contract::query,
+    );
+    Box::new(contract)
+}
+
+fn cw20_contract() -> Box<dyn Contract<Empty>> {
+    let contract = ContractWrapper::new(
+        cw20_base::contract::execute,
+        cw20_base::contract::instantiate,
+        cw20_base::contract::query,
+    );
+    Box::new(contract)
+}
+
+/// Instantiates the transfer contract with 10% fees alongside a cw20-base token where the sender holds
+/// 1000 tokens. Returns the app, the transfer contract address and the token address.
+fn setup() -> (App, Addr, Addr) {
+    let mut app = App::default();
+
+    let transfer_id = app.store_code(transfer_contract());
+    let transfer_addr = app
+        .instantiate_contract(
+            transfer_id,
+            Addr::unchecked(OWNER),
+            &InstantiateMsg {
+                fees: 1000,
+                fee_update_delay: None,
+                max_recipients: None,
+                pauser: None,
+                charge_transfer_fees: None,
+                fee_recipients: None,
+                fee_recipient: None,
+                referral_share: None,
+                denom_allowlist: None,
+            },
+            &[],
+            "transfer",
+            None,
+        )
+        .unwrap();
+
+    let cw20_id = app.store_code(cw20_contract());
+    let cw20_addr = app
+        .instantiate_contract(
+            cw20_id,
+            Addr::unchecked(OWNER),
+            &cw20_base::msg::InstantiateMsg {
+                name: "Token".to_owned(),
+                symbol: "TKN".to_owned(),
+                decimals: 6,
+                initial_balances: vec![Cw20Coin {
+                    address: SENDER.to_owned(),
+                    amount: Uint128::new(1000),
+                }],
+                mint: None,
+                marketing: None,
+            },
+            &[],
+            "token",
+            None,
+        )
+        .unwrap();
+
+    (app, transfer_addr, cw20_addr)
+}
+
+fn query_balance(app: &App, contract: &Addr, account: &str, denom: &str) -> Uint128 {
+    let res: GetBalanceResponse = app
+        .wrap()
+        .query_wasm_smart(
+            contract,
+            &QueryMsg::GetBalance {
+                account: account.to_owned(),
+                denom: denom.to_owned(),
+            },
+        )
+        .unwrap();
+    res.balance
+}
+
+fn query_cw20_balance(app: &App, token: &Addr, address: &str) -> Uint128 {
+    let res: BalanceResponse = app
+        .wrap()
+        .query_wasm_smart(
+            token,
+            &Cw20QueryMsg::Balance {
+                address: address.to_owned(),
+            },
+        )
+        .unwrap();
+    res.balance
+}
+
+#[test]
+fn cw20_send_and_withdraw() {
+    let (mut app, transfer_addr, cw20_addr) = setup();
+    let denom = format!("cw20:{}", cw20_addr);
+
+    // send 100 tokens through the contract to two accounts
+    let msg = Cw20ExecuteMsg::Send {
+        contract: transfer_addr.to_string(),
+        amount: Uint128::new(100),
+        msg: to_binary(&ReceiveMsg::Send {
+            account1: "account1".to_owned(),
+            account2: "account2".to_owned(),
+            referrer: None,
+        })
+        .unwrap(),
+    };
+    app.execute_contract(Addr::unchecked(SENDER), cw20_addr.clone(), &msg, &[])
+        .unwrap();
+
+    assert_eq!(Uint128::new(45), query_balance(&app, &transfer_addr, "account1", &denom));
+    assert_eq!(Uint128::new(45), query_balance(&app, &transfer_addr, "account2", &denom));
+    assert_eq!(Uint128::new(10), query_balance(&app, &transfer_addr, OWNER, &denom));
+
+    // the ledger matches the tokens held by the contract
+    let res: GetSolvencyResponse = app
+        .wrap()
+        .query_wasm_smart(
+            &transfer_addr,
+            &QueryMsg::GetSolvency {
+                denom: denom.clone(),
+            },
+        )
+        .unwrap();
+    assert_eq!(Uint128::new(100), res.liabilities);
+    assert_eq!(Uint128::new(100), res.balance);
+    assert!(res.solvent);
+
+    // account 1 withdraws part of its tokens, which are transferred by the token contract
+    let msg = ExecuteMsg::Withdraw {
+        amount: Uint128::new(25),
+        denom: denom.clone(),
+        recipient: None,
+        msg: None,
+    };
+    app.execute_contract(Addr::unchecked("account1"), transfer_addr.clone(), &msg, &[])
+        .unwrap();
+
+    assert_eq!(Uint128::new(25), query_cw20_balance(&app, &cw20_addr, "account1"));
+    assert_eq!(Uint128::new(20), query_balance(&app, &transfer_addr, "account1", &denom));
+
+    // the owner withdraws all of its fees
+    let msg = ExecuteMsg::WithdrawAll {
+        denom: denom.clone(),
+        recipient: None,
+        msg: None,
+    };
+    app.execute_contract(Addr::unchecked(OWNER), transfer_addr.clone(), &msg, &[])
+        .unwrap();
+
+    assert_eq!(Uint128::new(10), query_cw20_balance(&app, &cw20_addr, OWNER));
+    assert_eq!(Uint128::new(65), query_cw20_balance(&app, &cw20_addr, transfer_addr.as_str()));
+}
+
+#[test]
+fn cw20_send_split() {
+    let (mut app, transfer_addr, cw20_addr) = setup();
+    let denom = format!("cw20:{}", cw20_addr);
+
+    // 900 tokens are left after fees and split 2:1
+    let msg = Cw20ExecuteMsg::Send {
+        contract: transfer_addr.to_string(),
+        amount: Uint128::new(1000),
+        msg: to_binary(&ReceiveMsg::SendSplit {
+            recipients: vec![
+                Recipient { address: "account1".to_owned(), weight: 2 },
+                Recipient { address: "account2".to_owned(), weight: 1 },
+            ],
+        })
+        .unwrap(),
+    };
+    app.execute_contract(Addr::unchecked(SENDER), cw20_addr, &msg, &[])
+        .unwrap();
+
+    assert_eq!(Uint128::new(600), query_balance(&app, &transfer_addr, "account1", &denom));
+    assert_eq!(Uint128::new(300), query_balance(&app, &transfer_addr, "account2", &denom));
+    assert_eq!(Uint128::new(100), query_balance(&app, &transfer_addr, OWNER, &denom));
+}
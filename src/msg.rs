@@ -1,19 +1,152 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Binary, Coin, Timestamp, Uint128};
+use cw20::Cw20ReceiveMsg;
+use cw_utils::Expiration;
+
+use crate::state::{EscrowShare, FeeRecipient, PendingDenomFee, PendingFeeBounds, VestingSchedule};
 
 #[cw_serde]
 pub struct InstantiateMsg {
-    pub fees: u8,
+    /// Fees charged on sends in basis points, i.e. 25 is 0.25%
+    pub fees: u16,
+    /// Number of seconds a fee update is queued before it takes effect, defaults to one day
+    pub fee_update_delay: Option<u64>,
+    /// Maximum number of recipients of a single split send, defaults to 20
+    pub max_recipients: Option<u32>,
+    /// Address that can pause and unpause the contract alongside the owner
+    pub pauser: Option<String>,
+    /// Whether internal balance transfers are charged the send fees, defaults to false
+    pub charge_transfer_fees: Option<bool>,
+    /// Addresses the fees are split between in proportion to their weights, defaults to the fee recipient
+    pub fee_recipients: Option<Vec<Recipient>>,
+    /// Address that collects the fees instead of the owner, defaults to the owner
+    pub fee_recipient: Option<String>,
+    /// Share of the fee of a referred send credited to the referrer in basis points, defaults to 0
+    pub referral_share: Option<u16>,
+    /// Whether only denoms in the denom registry can be sent, defaults to false
+    pub denom_allowlist: Option<bool>,
+}
+
+#[cw_serde]
+pub struct Recipient {
+    pub address: String,
+    pub weight: u64,
 }
 
+#[cw_serde]
+pub struct MigrateMsg {}
+
 #[cw_serde]
 pub enum ExecuteMsg {
-    /// Sends funds and distributes them evenly between two account while adding up fees for the owner
-    Send {account1: String, account2: String},
-    /// Allows users to withdraw funds given an amount and a denom
-    Withdraw {amount : Uint128, denom : String},
-    /// Allows users to withdraw the maximum balance for a given denom
-    WithdrawAll {denom : String},
+    /// Sends funds and distributes them evenly between two account while adding up fees for the owner.
+    /// A share of the fees is credited to the referrer if one is given
+    Send {account1: String, account2: String, referrer: Option<String>},
+    /// Sends funds and distributes them between the recipients in proportion to their weights while
+    /// adding up fees for the owner
+    SendSplit {recipients: Vec<Recipient>},
+    /// Sends funds and distributes them evenly between two account like Send, but each share vests
+    /// linearly from start until end and nothing can be withdrawn before the cliff
+    SendVesting {account1: String, account2: String, start: Timestamp, cliff: Timestamp, end: Timestamp},
+    /// Sends funds into an escrow whose shares, weighted like SendSplit, the recipients must claim before
+    /// expires. Fees are taken when the escrow is created
+    SendEscrow {recipients: Vec<Recipient>, expires: Timestamp},
+    /// Allows a recipient of an escrow to claim its share into its balance before the escrow expires
+    Claim {id: u64},
+    /// Allows the sender of an escrow to take back the unclaimed shares into its balance after it expires
+    Refund {id: u64},
+    /// Sends funds into a pending payment that the arbiter can approve for the recipient or reject back to
+    /// the sender. Fees are only taken when the payment is approved
+    SendWithArbiter {recipient: String, arbiter: String, expires: Timestamp},
+    /// Allows the arbiter of a payment to credit it to the recipient, minus the fees, before it expires
+    Approve {id: u64},
+    /// Allows the arbiter of a payment to refund it to the sender
+    Reject {id: u64},
+    /// Refunds an expired payment to the sender. Anyone can refund a payment once it has expired
+    RefundPayment {id: u64},
+    /// Receives CW20 tokens and distributes them according to the embedded ReceiveMsg. CW20 balances
+    /// are tracked under the denom cw20:{contract_addr}
+    Receive(Cw20ReceiveMsg),
+    /// Allows users to withdraw funds given an amount and a denom. Funds are paid out to the recipient
+    /// (the sender by default), executing msg on it if the recipient is a contract
+    Withdraw {amount : Uint128, denom : String, recipient: Option<String>, msg: Option<Binary>},
+    /// Allows users to withdraw the maximum balance for a given denom. Funds are paid out to the recipient
+    /// (the sender by default), executing msg on it if the recipient is a contract
+    WithdrawAll {denom : String, recipient: Option<String>, msg: Option<Binary>},
+    /// Allows users to withdraw several coins at once in a single bank send
+    WithdrawMany {coins: Vec<Coin>},
+    /// Allows users to withdraw their entire balance of every denom at once
+    WithdrawEverything {},
+    /// Allows users to move part of their balance to another address within the contract without
+    /// sending any funds
+    TransferBalance {to: String, amount: Uint128, denom: String},
+    /// Allows users to let a spender withdraw up to an additional amount of a denom on their behalf,
+    /// optionally replacing the expiry of the allowance
+    IncreaseAllowance {spender: String, denom: String, amount: Uint128, expires: Option<Expiration>},
+    /// Allows users to lower the amount a spender can withdraw on their behalf, optionally replacing
+    /// the expiry of the allowance
+    DecreaseAllowance {spender: String, denom: String, amount: Uint128, expires: Option<Expiration>},
+    /// Allows a spender to withdraw from the balance of an owner that has given them an allowance.
+    /// Funds are paid out to the recipient, the spender by default
+    WithdrawFrom {owner: String, amount: Uint128, denom: String, recipient: Option<String>},
+    /// Allows the owner to propose a new owner that can accept ownership before the optional expiry
+    ProposeNewOwner {new_owner: String, expires: Option<Expiration>},
+    /// Allows the proposed owner to accept ownership of the contract
+    AcceptOwnership {},
+    /// Allows the owner to cancel a pending ownership proposal
+    CancelOwnershipProposal {},
+    /// Allows the owner to queue a fee update that takes effect after the configured delay
+    UpdateFees {fees: u16},
+    /// Allows the owner to update the addresses the fees are split between. An empty list sends all fees
+    /// to the fee recipient
+    UpdateFeeRecipients {recipients: Vec<Recipient>},
+    /// Allows the owner to change the address that collects the fees, or to collect them itself if none
+    UpdateFeeRecipient {fee_recipient: Option<String>},
+    /// Allows the owner to update the share of the fee of a referred send credited to the referrer
+    UpdateReferralShare {referral_share: u16},
+    /// Allows the owner to add a denom to the denom registry, or to update it if already registered
+    RegisterDenom {denom: String, display_name: String, decimals: u8, min_send: Option<Uint128>},
+    /// Allows the owner to remove a denom from the denom registry
+    DeregisterDenom {denom: String},
+    /// Allows the owner to switch between allowlist mode, where only registered denoms can be sent, and
+    /// open mode
+    SetDenomAllowlist {enabled: bool},
+    /// Allows the owner to queue the fees charged on sends of a denom, overriding the global fees once
+    /// the fee update delay has passed
+    SetDenomFee {denom: String, fees: u16},
+    /// Allows the owner to queue bounds on the fee charged on a send of a denom, which take effect once the
+    /// fee update delay has passed. Clearing both bounds removes them
+    SetFeeBounds {denom: String, min_fee: Option<Uint128>, max_fee: Option<Uint128>},
+    /// Allows the owner to queue clearing the fees of a denom so that it is charged the global fees again
+    ClearDenomFee {denom: String},
+    /// Allows the owner to exempt a sender from paying fees on their sends
+    AddFeeExempt {address: String},
+    /// Allows the owner to remove a sender from the fee exemptions
+    RemoveFeeExempt {address: String},
+    /// Allows the owner to pay out funds held by the contract beyond the total owed to users
+    SweepSurplus {denom: String, recipient: String},
+    /// Allows the owner or pauser to pause deposits (sends) and/or withdrawals
+    Pause {deposits: bool, withdrawals: bool},
+    /// Allows the owner or pauser to unpause deposits (sends) and/or withdrawals
+    Unpause {deposits: bool, withdrawals: bool},
+    /// Allows the owner to set or remove the pauser
+    UpdatePauser {pauser: Option<String>},
+}
+
+/// Payload embedded in a Cw20ReceiveMsg describing how the received tokens are distributed
+#[cw_serde]
+pub enum ReceiveMsg {
+    /// Distributes the tokens evenly between two account while adding up fees for the owner. A share of
+    /// the fees is credited to the referrer if one is given
+    Send {account1: String, account2: String, referrer: Option<String>},
+    /// Distributes the tokens between the recipients in proportion to their weights while adding up
+    /// fees for the owner
+    SendSplit {recipients: Vec<Recipient>},
+    /// Sends the tokens into an escrow like the native SendEscrow
+    SendEscrow {recipients: Vec<Recipient>, expires: Timestamp},
+    /// Sends the tokens into a pending payment like the native SendWithArbiter
+    SendWithArbiter {recipient: String, arbiter: String, expires: Timestamp},
+    /// Distributes the tokens with a vesting schedule like the native SendVesting
+    SendVesting {account1: String, account2: String, start: Timestamp, cliff: Timestamp, end: Timestamp},
 }
 
 #[cw_serde]
@@ -27,10 +160,92 @@ pub enum QueryMsg {
     #[returns(GetFeesResponse)]
     GetFees {},
 
+    /// Returns the administrative owner alongside the address that collects the fees and other settings
+    #[returns(GetConfigResponse)]
+    GetConfig {},
+
+    /// Returns the denoms in the denom registry with their metadata, paginated by denom
+    #[returns(ListSupportedDenomsResponse)]
+    ListSupportedDenoms {start_after: Option<String>, limit: Option<u32>},
+
+    /// Returns an open escrow
+    #[returns(EscrowResponse)]
+    GetEscrow {id: u64},
+
+    /// Returns the open escrows created by a sender, paginated by id
+    #[returns(ListEscrowsResponse)]
+    ListEscrowsBySender {sender: String, start_after: Option<u64>, limit: Option<u32>},
+
+    /// Returns the open escrows with an unclaimed share for a recipient, paginated by id
+    #[returns(ListEscrowsResponse)]
+    ListEscrowsByRecipient {recipient: String, start_after: Option<u64>, limit: Option<u32>},
+
+    /// Returns a pending arbiter payment
+    #[returns(ArbiterPaymentResponse)]
+    GetArbiterPayment {id: u64},
+
+    /// Returns the total fees credited to a referrer for every denom
+    #[returns(GetReferralStatsResponse)]
+    GetReferralStats {referrer: String},
+
+    /// Returns the addresses the fees are split between and their weights
+    #[returns(GetFeeRecipientsResponse)]
+    GetFeeRecipients {},
+
+    /// Returns the fees charged on sends of a denom, whether they are the global default and the bounds on
+    /// the fee charged
+    #[returns(GetFeeForDenomResponse)]
+    GetFeeForDenom {denom: String},
+
+    /// Returns the senders that are exempt from fees, paginated by address
+    #[returns(ListFeeExemptResponse)]
+    ListFeeExempt {start_after: Option<String>, limit: Option<u32>},
+
+    /// Returns the vesting schedules locking the balance of the user for a given denom
+    #[returns(GetVestingScheduleResponse)]
+    GetVestingSchedule {account: String, denom: String},
+
+    /// Returns how much of the balance of the user for a given denom is vested and can be withdrawn
+    #[returns(GetWithdrawableResponse)]
+    GetWithdrawable {account: String, denom: String},
+
     /// Returns a human-readable representation of the balance of the user 
     /// for a given denom
     #[returns(GetBalanceResponse)]
-    GetBalance {account : String, denom: String}
+    GetBalance {account : String, denom: String},
+
+    /// Returns every non-zero balance of the user ordered by denom, starting after
+    /// the given denom and returning at most limit coins per page
+    #[returns(GetAllBalancesResponse)]
+    GetAllBalances {account: String, start_after: Option<String>, limit: Option<u32>},
+
+    /// Returns the total amount of a denom owed to users alongside the contract's
+    /// bank balance of that denom
+    #[returns(GetSolvencyResponse)]
+    GetSolvency {denom: String},
+
+    /// Returns whether deposits and withdrawals are currently paused along with the pauser
+    #[returns(GetPauseStatusResponse)]
+    GetPauseStatus {},
+
+    /// Returns how much of a denom a spender can withdraw on behalf of an owner
+    #[returns(GetAllowanceResponse)]
+    GetAllowance {owner: String, spender: String, denom: String},
+
+    /// Returns every allowance given by an owner in storage order, starting after the
+    /// given (spender, denom) and returning at most limit allowances per page
+    #[returns(AllAllowancesResponse)]
+    AllAllowances {owner: String, start_after: Option<(String, String)>, limit: Option<u32>},
+
+    /// Returns the proposed owner and the expiry of the proposal if an ownership
+    /// transfer is pending
+    #[returns(GetPendingOwnerResponse)]
+    GetPendingOwner {},
+
+    /// Returns the queued fee update and when it activates, if one is pending,
+    /// along with the configured update delay
+    #[returns(GetPendingConfigResponse)]
+    GetPendingConfig {},
 }
 
 
@@ -41,7 +256,95 @@ pub struct GetOwnerResponse {
 
 #[cw_serde]
 pub struct GetFeesResponse {
-    pub fees: u8,
+    pub fees: u16,
+}
+
+#[cw_serde]
+pub struct GetConfigResponse {
+    pub owner: Addr,
+    pub fee_recipients: Vec<FeeRecipient>,
+    pub max_recipients: u32,
+    pub charge_transfer_fees: bool,
+    pub referral_share: u16,
+    pub denom_allowlist: bool,
+}
+
+#[cw_serde]
+pub struct SupportedDenom {
+    pub denom: String,
+    pub display_name: String,
+    pub decimals: u8,
+    pub min_send: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct ListSupportedDenomsResponse {
+    pub denom_allowlist: bool,
+    pub denoms: Vec<SupportedDenom>,
+}
+
+#[cw_serde]
+pub struct EscrowResponse {
+    pub id: u64,
+    pub sender: Addr,
+    pub shares: Vec<EscrowShare>,
+    pub expires: Timestamp,
+}
+
+#[cw_serde]
+pub struct ListEscrowsResponse {
+    pub escrows: Vec<EscrowResponse>,
+}
+
+#[cw_serde]
+pub struct ArbiterPaymentResponse {
+    pub id: u64,
+    pub sender: Addr,
+    pub recipient: Addr,
+    pub arbiter: Addr,
+    pub funds: Vec<Coin>,
+    pub expires: Timestamp,
+}
+
+#[cw_serde]
+pub struct GetVestingScheduleResponse {
+    pub schedules: Vec<VestingSchedule>,
+    pub vested: Uint128,
+    pub locked: Uint128,
+}
+
+#[cw_serde]
+pub struct GetWithdrawableResponse {
+    pub balance: Uint128,
+    pub locked: Uint128,
+    pub withdrawable: Uint128,
+}
+
+#[cw_serde]
+pub struct GetReferralStatsResponse {
+    pub referrer: Addr,
+    pub earnings: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct GetFeeRecipientsResponse {
+    pub recipients: Vec<FeeRecipient>,
+}
+
+#[cw_serde]
+pub struct GetFeeForDenomResponse {
+    pub denom: String,
+    pub fees: u16,
+    pub is_default: bool,
+    pub min_fee: Option<Uint128>,
+    pub max_fee: Option<Uint128>,
+    pub pending_fees: Option<PendingDenomFee>,
+    pub pending_bounds: Option<PendingFeeBounds>,
+}
+
+#[cw_serde]
+pub struct ListFeeExemptResponse {
+    pub addresses: Vec<Addr>,
 }
 
 // We define a custom struct for each query response
@@ -49,3 +352,55 @@ pub struct GetFeesResponse {
 pub struct GetBalanceResponse {
     pub balance: Uint128,
 }
+
+#[cw_serde]
+pub struct GetAllBalancesResponse {
+    pub balances: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct GetSolvencyResponse {
+    pub denom: String,
+    pub liabilities: Uint128,
+    pub balance: Uint128,
+    pub solvent: bool,
+}
+
+#[cw_serde]
+pub struct GetPauseStatusResponse {
+    pub deposits_paused: bool,
+    pub withdrawals_paused: bool,
+    pub pauser: Option<Addr>,
+}
+
+#[cw_serde]
+pub struct GetAllowanceResponse {
+    pub amount: Uint128,
+    pub expires: Expiration,
+}
+
+#[cw_serde]
+pub struct AllowanceInfo {
+    pub spender: Addr,
+    pub denom: String,
+    pub amount: Uint128,
+    pub expires: Expiration,
+}
+
+#[cw_serde]
+pub struct AllAllowancesResponse {
+    pub allowances: Vec<AllowanceInfo>,
+}
+
+#[cw_serde]
+pub struct GetPendingOwnerResponse {
+    pub pending_owner: Option<Addr>,
+    pub expires: Option<Expiration>,
+}
+
+#[cw_serde]
+pub struct GetPendingConfigResponse {
+    pub fees: Option<u16>,
+    pub activates_at: Option<Timestamp>,
+    pub fee_update_delay: u64,
+}
@@ -1,18 +1,255 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Uint128};
+use cosmwasm_std::{Addr, Coin, Empty, Timestamp, Uint128};
 use cw_storage_plus::{Item, Map};
+use cw_utils::Expiration;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct State {
+    /// The administrative owner of the contract.
     pub owner: Addr,
-    pub fees: u8,
+    /// The number of basis points of each send that is taken as fees. Must be at most 10_000.
+    pub fees: u16,
+    /// The number of seconds a fee update by the owner is queued before it takes effect.
+    pub fee_update_delay: u64,
+    /// The maximum number of recipients of a single split send.
+    pub max_recipients: u32,
+    /// An optional address that can pause and unpause deposits and withdrawals alongside the owner.
+    pub pauser: Option<Addr>,
+    /// Whether messages that bring funds into the contract, such as sends, are paused.
+    pub deposits_paused: bool,
+    /// Whether withdrawals and balance transfers are paused.
+    pub withdrawals_paused: bool,
+    /// Whether internal balance transfers are charged the send fees.
+    pub charge_transfer_fees: bool,
+    /// The weighted addresses the fees are split between. All fees go to the fee recipient when empty.
+    pub fee_recipients: Vec<FeeRecipient>,
+    /// An optional address, such as a treasury, that collects the fees instead of the owner. Cannot be set
+    /// alongside weighted fee recipients.
+    pub fee_recipient: Option<Addr>,
+    /// The number of basis points of the fee of a referred send that is credited to the referrer.
+    pub referral_share: u16,
+    /// Whether only denoms in the denom registry can be sent.
+    pub denom_allowlist: bool,
 }
 
-/// State tracks the owner of the contract as well as the fees that are removed per send tx. Fees must
-/// be a number less than 100. fees is the percentage of each transaction that will go to the owner.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FeeRecipient {
+    pub address: Addr,
+    pub weight: u64,
+}
+
+/// State tracks the owner of the contract as well as the fees that are removed per send tx and the other
+/// settings of the contract, each documented on State.
 pub const STATE: Item<State> = Item::new("state");
 
-/// Balances tracks the amount of each coin each registered address is permitted to withdraw.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct StateV0_1_0 {
+    pub owner: Addr,
+    pub fees: u8,
+}
+
+/// State as stored by version 0.1.0 of the contract, where fees was a whole percentage. Only used to read
+/// the old layout when migrating.
+pub const STATE_V0_1_0: Item<StateV0_1_0> = Item::new("state");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingConfig {
+    pub fees: u16,
+    pub activates_at: Timestamp,
+}
+
+/// Pending config tracks a fee update queued by the owner. The new fees only take effect once the block
+/// time reaches activates_at, giving users advance notice of the change.
+pub const PENDING_CONFIG: Item<PendingConfig> = Item::new("pending_config");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingOwner {
+    pub new_owner: Addr,
+    pub expires: Option<Expiration>,
+}
+
+/// Pending owner tracks an ownership transfer that has been proposed by the current owner but not yet
+/// accepted by the new owner. The proposal can optionally expire, after which it can no longer be accepted.
+pub const PENDING_OWNER: Item<PendingOwner> = Item::new("pending_owner");
+
+/// Prefix of the denoms in BALANCES and TOTAL_LIABILITIES that track CW20 tokens. It is followed by the
+/// address of the token contract i.e. cw20:{contract_addr}.
+pub const CW20_PREFIX: &str = "cw20:";
+
+/// AssetInfo identifies the asset behind a denom, which is either a native coin or a CW20 token.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub enum AssetInfo {
+    Native(String),
+    Cw20(Addr),
+}
+
+impl AssetInfo {
+    /// Parses a denom as stored in BALANCES. CW20 token addresses are validated when they are first
+    /// credited in receive so they are not validated again here.
+    pub fn from_denom(denom: &str) -> Self {
+        match denom.strip_prefix(CW20_PREFIX) {
+            Some(contract_addr) => AssetInfo::Cw20(Addr::unchecked(contract_addr)),
+            None => AssetInfo::Native(denom.to_owned()),
+        }
+    }
+
+    /// Returns the denom under which the asset is stored in BALANCES.
+    pub fn denom(&self) -> String {
+        match self {
+            AssetInfo::Native(denom) => denom.clone(),
+            AssetInfo::Cw20(contract_addr) => format!("{}{}", CW20_PREFIX, contract_addr),
+        }
+    }
+}
+
+/// Balances tracks the amount of each coin each registered address is permitted to withdraw. Native coins
+/// are keyed by their denom while CW20 tokens are keyed by cw20:{contract_addr}.
 pub const BALANCES: Map<(&Addr, String), Uint128> = Map::new("balances");
+
+/// Total liabilities tracks, per denom, the sum of every balance in BALANCES, including unvested balances,
+/// and of the funds held in escrows and arbiter payments i.e. the total amount of each coin the contract owes
+/// to its users.
+pub const TOTAL_LIABILITIES: Map<String, Uint128> = Map::new("total_liabilities");
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Allowance {
+    pub amount: Uint128,
+    pub expires: Expiration,
+}
+
+/// Allowances tracks, for each (owner, spender, denom), how much of the owner's balance the spender is
+/// permitted to withdraw on their behalf and when that permission expires.
+pub const ALLOWANCES: Map<(&Addr, &Addr, String), Allowance> = Map::new("allowances");
+
+/// Denom fees tracks the fees, in basis points, charged on sends of specific denoms. Denoms without an entry
+/// are charged the global State.fees.
+pub const DENOM_FEES: Map<String, u16> = Map::new("denom_fees");
+
+/// Fee exempt tracks the set of senders, such as the treasury or partner contracts, that the owner does not
+/// charge fees on their sends.
+pub const FEE_EXEMPT: Map<&Addr, Empty> = Map::new("fee_exempt");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct FeeBounds {
+    pub min_fee: Option<Uint128>,
+    pub max_fee: Option<Uint128>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingDenomFee {
+    pub fees: Option<u16>,
+    pub activates_at: Timestamp,
+}
+
+/// Pending denom fees tracks the denom -> a change to its fees queued by the owner, where None clears the
+/// denom's fees. Like PENDING_CONFIG the change only takes effect once the block time reaches activates_at.
+pub const PENDING_DENOM_FEES: Map<String, PendingDenomFee> = Map::new("pending_denom_fees");
+
+/// Fee bounds tracks the denom -> the smallest and largest fee charged on a send of that denom. The bounds
+/// are applied after the percentage is calculated.
+pub const FEE_BOUNDS: Map<String, FeeBounds> = Map::new("fee_bounds");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PendingFeeBounds {
+    pub bounds: FeeBounds,
+    pub activates_at: Timestamp,
+}
+
+/// Pending fee bounds tracks the denom -> new bounds on its fee queued by the owner, where clearing both
+/// bounds removes them. Like PENDING_CONFIG the bounds only take effect once the block time reaches
+/// activates_at.
+pub const PENDING_FEE_BOUNDS: Map<String, PendingFeeBounds> = Map::new("pending_fee_bounds");
+
+/// Referral earnings tracks the (referrer, denom) -> the total amount of fees credited to the referrer.
+pub const REFERRAL_EARNINGS: Map<(&Addr, String), Uint128> = Map::new("referral_earnings");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DenomInfo {
+    pub display_name: String,
+    pub decimals: u8,
+    pub min_send: Option<Uint128>,
+}
+
+/// Supported denoms tracks the denom registry, denom -> the display name, decimals and minimum send amount
+/// of that denom. In allowlist mode only registered denoms can be sent.
+pub const SUPPORTED_DENOMS: Map<String, DenomInfo> = Map::new("supported_denoms");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct EscrowShare {
+    pub address: Addr,
+    pub funds: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Escrow {
+    pub sender: Addr,
+    pub shares: Vec<EscrowShare>,
+    pub expires: Timestamp,
+}
+
+/// Escrows tracks the escrow id -> the sender, the unclaimed share of each recipient after fees and the time
+/// after which the sender can refund the unclaimed shares. Escrows are removed once every share is claimed or
+/// refunded.
+pub const ESCROWS: Map<u64, Escrow> = Map::new("escrows");
+
+/// Escrow count tracks the number of escrows created, which is used as the id of the next escrow.
+pub const ESCROW_COUNT: Item<u64> = Item::new("escrow_count");
+
+/// Escrows by sender and escrows by recipient index the open escrows, (address, escrow id) -> Empty, so
+/// that they can be listed for an address.
+pub const ESCROWS_BY_SENDER: Map<(&Addr, u64), Empty> = Map::new("escrows_by_sender");
+pub const ESCROWS_BY_RECIPIENT: Map<(&Addr, u64), Empty> = Map::new("escrows_by_recipient");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ArbiterPayment {
+    pub sender: Addr,
+    pub recipient: Addr,
+    pub arbiter: Addr,
+    pub funds: Vec<Coin>,
+    pub expires: Timestamp,
+}
+
+/// Arbiter payments tracks the payment id -> a pending payment that the arbiter can approve for the
+/// recipient or reject back to the sender before it expires. Payments are removed once they are settled.
+pub const ARBITER_PAYMENTS: Map<u64, ArbiterPayment> = Map::new("arbiter_payments");
+
+/// Arbiter payment count tracks the number of arbiter payments created, which is used as the id of the next
+/// payment.
+pub const ARBITER_PAYMENT_COUNT: Item<u64> = Item::new("arbiter_payment_count");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct VestingSchedule {
+    pub sender: Addr,
+    pub total: Uint128,
+    pub start: Timestamp,
+    pub cliff: Timestamp,
+    pub end: Timestamp,
+}
+
+impl VestingSchedule {
+    /// Returns the amount vested at a time. Nothing is vested before the cliff, after which the total vests
+    /// linearly from start until it is fully vested at end.
+    pub fn vested(&self, time: Timestamp) -> Uint128 {
+        if time < self.cliff {
+            Uint128::zero()
+        } else if time >= self.end {
+            self.total
+        } else {
+            self.total.multiply_ratio(
+                time.seconds() - self.start.seconds(),
+                self.end.seconds() - self.start.seconds(),
+            )
+        }
+    }
+}
+
+/// Vesting schedules tracks the (account, denom, schedule id) -> a schedule locking part of the balance of the
+/// account in BALANCES, along with the sender that added it. Only the vested portion of each schedule can be
+/// withdrawn, and schedules are removed once fully vested.
+pub const VESTING_SCHEDULES: Map<(&Addr, String, u64), VestingSchedule> = Map::new("vesting_schedules");
+
+/// Vesting schedule count tracks the number of vesting schedules created, which is used as the id of the
+/// next schedule.
+pub const VESTING_SCHEDULE_COUNT: Item<u64> = Item::new("vesting_schedule_count");